@@ -35,8 +35,8 @@ impl slack::EventHandler for MyHandler {
         println!("on_ping");
     }
 
-    fn on_close(&mut self, cli: &mut slack::RtmClient) {
-        println!("on_close");
+    fn on_close(&mut self, cli: &mut slack::RtmClient, code: Option<u16>, reason: &str) {
+        println!("on_close(code: {:?}, reason: {:?})", code, reason);
     }
 
     fn on_connect(&mut self, cli: &mut slack::RtmClient) {
@@ -0,0 +1,179 @@
+//
+// Copyright 2014-2016 the slack-rs authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Sending ephemeral, /me and scheduled messages, and getting message permalinks.
+
+use hyper;
+
+use conversations::ResponseMetadata;
+use error::Error;
+use web;
+use RtmClient;
+
+/// Response of https://api.slack.com/methods/chat.meMessage
+#[derive(Clone, Debug, RustcDecodable)]
+pub struct MeMessageResponse {
+    pub channel: String,
+    pub ts: String,
+}
+
+/// Response of https://api.slack.com/methods/chat.scheduleMessage
+#[derive(Clone, Debug, RustcDecodable)]
+pub struct ScheduleMessageResponse {
+    pub channel: String,
+    pub scheduled_message_id: String,
+    pub post_at: i64,
+}
+
+/// A message waiting to be posted, as listed by `RtmClient::list_scheduled_messages`.
+#[derive(Clone, Debug, RustcDecodable)]
+pub struct ScheduledMessage {
+    pub id: String,
+    pub channel_id: String,
+    pub post_at: i64,
+    pub date_created: i64,
+    pub text: Option<String>,
+}
+
+/// Response of https://api.slack.com/methods/chat.scheduledMessages.list
+#[derive(Clone, Debug, RustcDecodable)]
+pub struct ScheduledMessagesResponse {
+    pub scheduled_messages: Vec<ScheduledMessage>,
+    pub response_metadata: Option<ResponseMetadata>,
+}
+
+#[derive(RustcDecodable)]
+struct EphemeralResponse {
+    message_ts: String,
+}
+
+#[derive(RustcDecodable)]
+struct PermalinkResponse {
+    permalink: String,
+}
+
+impl RtmClient {
+    /// Wraps https://api.slack.com/methods/chat.postEphemeral to post a message to a channel
+    /// that is only visible to user_id. Returns the timestamp of the message.
+    /// If channel starts with a # then it will be looked up with get_channel_id
+    pub fn post_ephemeral(&self,
+                          channel: &str,
+                          user_id: &str,
+                          text: &str,
+                          attachments: Option<&str>)
+                          -> Result<String, Error> {
+        let chan_id = try!(self.evaluate_channel_id(channel));
+        let mut params = vec![("token", &self.token[..]),
+                              ("channel", &chan_id[..]),
+                              ("user", user_id),
+                              ("text", text),
+                              ("as_user", "true")];
+        web::push_opt(&mut params, "attachments", attachments);
+
+        let client = hyper::Client::new();
+        let res: EphemeralResponse = try!(web::call(&client, "chat.postEphemeral", &params));
+        Ok(res.message_ts)
+    }
+
+    /// Wraps https://api.slack.com/methods/chat.meMessage to post a /me message to a channel.
+    /// If channel starts with a # then it will be looked up with get_channel_id
+    pub fn me_message(&self, channel: &str, text: &str) -> Result<MeMessageResponse, Error> {
+        let chan_id = try!(self.evaluate_channel_id(channel));
+        let client = hyper::Client::new();
+        web::call(&client,
+                  "chat.meMessage",
+                  &[("token", &self.token[..]), ("channel", &chan_id[..]), ("text", text)])
+    }
+
+    /// Wraps https://api.slack.com/methods/chat.scheduleMessage to post a message to a channel
+    /// at post_at, a unix timestamp up to 120 days in the future. The message is posted in the
+    /// thread of thread_ts if set.
+    /// If channel starts with a # then it will be looked up with get_channel_id
+    pub fn schedule_message(&self,
+                            channel: &str,
+                            text: &str,
+                            post_at: i64,
+                            attachments: Option<&str>,
+                            thread_ts: Option<&str>)
+                            -> Result<ScheduleMessageResponse, Error> {
+        let chan_id = try!(self.evaluate_channel_id(channel));
+        let post_at = post_at.to_string();
+        let mut params = vec![("token", &self.token[..]),
+                              ("channel", &chan_id[..]),
+                              ("text", text),
+                              ("post_at", &post_at[..]),
+                              ("as_user", "true")];
+        web::push_opt(&mut params, "attachments", attachments);
+        web::push_opt(&mut params, "thread_ts", thread_ts);
+
+        let client = hyper::Client::new();
+        web::call(&client, "chat.scheduleMessage", &params)
+    }
+
+    /// Wraps https://api.slack.com/methods/chat.deleteScheduledMessage to cancel a scheduled
+    /// message before it is posted.
+    /// If channel starts with a # then it will be looked up with get_channel_id
+    pub fn delete_scheduled_message(&self, channel: &str, scheduled_message_id: &str) -> Result<(), Error> {
+        let chan_id = try!(self.evaluate_channel_id(channel));
+        let client = hyper::Client::new();
+        try!(web::call_json(&client,
+                            "chat.deleteScheduledMessage",
+                            &[("token", &self.token[..]),
+                              ("channel", &chan_id[..]),
+                              ("scheduled_message_id", scheduled_message_id)]));
+        Ok(())
+    }
+
+    /// Wraps https://api.slack.com/methods/chat.scheduledMessages.list to list the messages
+    /// waiting to be posted, in all channels or only in channel. Pass the `next_cursor` of a
+    /// response as cursor to get the next page.
+    pub fn list_scheduled_messages(&self,
+                                   channel: Option<&str>,
+                                   oldest: Option<&str>,
+                                   latest: Option<&str>,
+                                   limit: Option<u32>,
+                                   cursor: Option<&str>)
+                                   -> Result<ScheduledMessagesResponse, Error> {
+        let chan_id = match channel {
+            Some(channel) => Some(try!(self.evaluate_channel_id(channel))),
+            None => None,
+        };
+        let limit = limit.map(|l| l.to_string());
+        let mut params = vec![("token", &self.token[..])];
+        web::push_opt(&mut params, "channel", chan_id.as_ref().map(|c| &c[..]));
+        web::push_opt(&mut params, "oldest", oldest);
+        web::push_opt(&mut params, "latest", latest);
+        web::push_opt(&mut params, "limit", limit.as_ref().map(|l| &l[..]));
+        web::push_opt(&mut params, "cursor", cursor);
+
+        let client = hyper::Client::new();
+        web::call(&client, "chat.scheduledMessages.list", &params)
+    }
+
+    /// Wraps https://api.slack.com/methods/chat.getPermalink to get a permanent link to a
+    /// message, e.g. to link back to a thread.
+    /// If channel starts with a # then it will be looked up with get_channel_id
+    pub fn get_permalink(&self, channel: &str, message_ts: &str) -> Result<String, Error> {
+        let chan_id = try!(self.evaluate_channel_id(channel));
+        let client = hyper::Client::new();
+        let res: PermalinkResponse = try!(web::call(&client,
+                                                    "chat.getPermalink",
+                                                    &[("token", &self.token[..]),
+                                                      ("channel", &chan_id[..]),
+                                                      ("message_ts", message_ts)]));
+        Ok(res.permalink)
+    }
+}
@@ -0,0 +1,415 @@
+//
+// Copyright 2014-2016 the slack-rs authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Wrappers for the [`conversations.*`](https://api.slack.com/docs/conversations-api)
+//! methods, which work the same for public channels, private channels (groups),
+//! direct messages and multi-party direct messages.
+//!
+//! Everywhere a channel is taken it can be a channel id of any type, or a
+//! `#name` which is looked up like in `RtmClient::send_message`.
+
+use hyper;
+use rustc_serialize::Decodable;
+
+use api::Message;
+use error::Error;
+use web;
+use RtmClient;
+
+/// The topic or purpose of a conversation.
+#[derive(Clone, Debug, RustcDecodable)]
+pub struct Topic {
+    pub value: String,
+    pub creator: String,
+    pub last_set: i64,
+}
+
+/// A channel, group, im or mpim as returned by the conversations methods.
+#[derive(Clone, Debug, RustcDecodable)]
+pub struct Conversation {
+    pub id: String,
+    pub name: Option<String>,
+    pub is_channel: Option<bool>,
+    pub is_group: Option<bool>,
+    pub is_im: Option<bool>,
+    pub is_mpim: Option<bool>,
+    pub is_private: Option<bool>,
+    pub is_archived: Option<bool>,
+    pub is_member: Option<bool>,
+    pub created: Option<i64>,
+    pub creator: Option<String>,
+    /// The other user of an im.
+    pub user: Option<String>,
+    pub topic: Option<Topic>,
+    pub purpose: Option<Topic>,
+    pub num_members: Option<u32>,
+}
+
+/// Cursor to fetch the next page of a paginated response.
+#[derive(Clone, Debug, RustcDecodable)]
+pub struct ResponseMetadata {
+    pub next_cursor: Option<String>,
+}
+
+/// Response of https://api.slack.com/methods/conversations.history and
+/// https://api.slack.com/methods/conversations.replies
+#[derive(Clone, Debug, RustcDecodable)]
+pub struct HistoryResponse {
+    pub messages: Vec<Message>,
+    pub has_more: Option<bool>,
+    pub response_metadata: Option<ResponseMetadata>,
+}
+
+/// Response of the conversations methods that return a single conversation.
+#[derive(Clone, Debug, RustcDecodable)]
+pub struct ConversationResponse {
+    pub channel: Conversation,
+}
+
+/// Response of https://api.slack.com/methods/conversations.list
+#[derive(Clone, Debug, RustcDecodable)]
+pub struct ListResponse {
+    pub channels: Vec<Conversation>,
+    pub response_metadata: Option<ResponseMetadata>,
+}
+
+/// Response of https://api.slack.com/methods/conversations.members
+#[derive(Clone, Debug, RustcDecodable)]
+pub struct MembersResponse {
+    pub members: Vec<String>,
+    pub response_metadata: Option<ResponseMetadata>,
+}
+
+fn bool_param(value: Option<bool>) -> Option<&'static str> {
+    value.map(|v| if v { "true" } else { "false" })
+}
+
+/// A call to a conversations method, built separately from sending it.
+#[derive(Clone, Debug, PartialEq)]
+struct Request {
+    method: &'static str,
+    /// The params besides the token.
+    params: Vec<(&'static str, String)>,
+}
+
+impl Request {
+    fn new(method: &'static str) -> Request {
+        Request {
+            method: method,
+            params: Vec::new(),
+        }
+    }
+
+    fn param(mut self, name: &'static str, value: &str) -> Request {
+        self.params.push((name, value.to_string()));
+        self
+    }
+
+    /// Adds the param if value is set, see `web::push_opt`.
+    fn opt(self, name: &'static str, value: Option<&str>) -> Request {
+        match value {
+            Some(value) => self.param(name, value),
+            None => self,
+        }
+    }
+
+    /// Returns the params with the token first.
+    fn with_token<'a>(&'a self, token: &'a str) -> Vec<(&'a str, &'a str)> {
+        let mut params = vec![("token", token)];
+        params.extend(self.params.iter().map(|&(name, ref value)| (name, &value[..])));
+        params
+    }
+
+    /// Calls the method with token and decodes the response into T.
+    fn send<T: Decodable>(&self, token: &str) -> Result<T, Error> {
+        let client = hyper::Client::new();
+        web::call(&client, self.method, &self.with_token(token))
+    }
+
+    /// Calls the method with token, ignoring the response besides its `ok`.
+    fn send_ok(&self, token: &str) -> Result<(), Error> {
+        let client = hyper::Client::new();
+        try!(web::call_json(&client, self.method, &self.with_token(token)));
+        Ok(())
+    }
+}
+
+impl RtmClient {
+    /// Wraps https://api.slack.com/methods/conversations.history to fetch a page of
+    /// messages and events from a conversation.
+    pub fn conversations_history(&self,
+                                 channel: &str,
+                                 cursor: Option<&str>,
+                                 latest: Option<&str>,
+                                 oldest: Option<&str>,
+                                 inclusive: Option<bool>,
+                                 limit: Option<u32>)
+                                 -> Result<HistoryResponse, Error> {
+        let chan_id = try!(self.evaluate_channel_id(channel));
+        history_request("conversations.history", &chan_id, cursor, latest, oldest, inclusive, limit)
+            .send(&self.token)
+    }
+
+    /// Wraps https://api.slack.com/methods/conversations.replies to fetch a page of the
+    /// replies in the thread started by the message at ts.
+    pub fn conversations_replies(&self,
+                                 channel: &str,
+                                 ts: &str,
+                                 cursor: Option<&str>,
+                                 latest: Option<&str>,
+                                 oldest: Option<&str>,
+                                 inclusive: Option<bool>,
+                                 limit: Option<u32>)
+                                 -> Result<HistoryResponse, Error> {
+        let chan_id = try!(self.evaluate_channel_id(channel));
+        history_request("conversations.replies", &chan_id, cursor, latest, oldest, inclusive, limit)
+            .param("ts", ts)
+            .send(&self.token)
+    }
+
+    /// Wraps https://api.slack.com/methods/conversations.info to get information about a
+    /// conversation.
+    pub fn conversations_info(&self, channel: &str, include_num_members: Option<bool>) -> Result<Conversation, Error> {
+        let chan_id = try!(self.evaluate_channel_id(channel));
+        let res: ConversationResponse = try!(channel_request("conversations.info", &chan_id)
+                                                 .opt("include_num_members", bool_param(include_num_members))
+                                                 .send(&self.token));
+        Ok(res.channel)
+    }
+
+    /// Wraps https://api.slack.com/methods/conversations.list to fetch a page of
+    /// conversations. types is a comma separated list of
+    /// `public_channel`, `private_channel`, `mpim` and `im`.
+    pub fn conversations_list(&self,
+                              types: Option<&str>,
+                              exclude_archived: Option<bool>,
+                              cursor: Option<&str>,
+                              limit: Option<u32>)
+                              -> Result<ListResponse, Error> {
+        list_request(types, exclude_archived, cursor, limit).send(&self.token)
+    }
+
+    /// Wraps https://api.slack.com/methods/conversations.members to fetch a page of the
+    /// ids of the members of a conversation.
+    pub fn conversations_members(&self,
+                                 channel: &str,
+                                 cursor: Option<&str>,
+                                 limit: Option<u32>)
+                                 -> Result<MembersResponse, Error> {
+        let chan_id = try!(self.evaluate_channel_id(channel));
+        members_request(&chan_id, cursor, limit).send(&self.token)
+    }
+
+    /// Wraps https://api.slack.com/methods/conversations.open to open an im with one user,
+    /// or an mpim with several.
+    pub fn conversations_open(&self, users: &[&str]) -> Result<Conversation, Error> {
+        let res: ConversationResponse = try!(Request::new("conversations.open")
+                                                 .param("users", &users.join(","))
+                                                 .send(&self.token));
+        Ok(res.channel)
+    }
+
+    /// Wraps https://api.slack.com/methods/conversations.join to join a public channel.
+    pub fn conversations_join(&self, channel: &str) -> Result<Conversation, Error> {
+        let chan_id = try!(self.evaluate_channel_id(channel));
+        let res: ConversationResponse = try!(channel_request("conversations.join", &chan_id).send(&self.token));
+        Ok(res.channel)
+    }
+
+    /// Wraps https://api.slack.com/methods/conversations.leave to leave a conversation.
+    pub fn conversations_leave(&self, channel: &str) -> Result<(), Error> {
+        let chan_id = try!(self.evaluate_channel_id(channel));
+        channel_request("conversations.leave", &chan_id).send_ok(&self.token)
+    }
+
+    /// Wraps https://api.slack.com/methods/conversations.invite to invite users to a
+    /// conversation.
+    pub fn conversations_invite(&self, channel: &str, users: &[&str]) -> Result<Conversation, Error> {
+        let chan_id = try!(self.evaluate_channel_id(channel));
+        let res: ConversationResponse = try!(channel_request("conversations.invite", &chan_id)
+                                                 .param("users", &users.join(","))
+                                                 .send(&self.token));
+        Ok(res.channel)
+    }
+
+    /// Wraps https://api.slack.com/methods/conversations.kick to remove a user from a
+    /// conversation.
+    pub fn conversations_kick(&self, channel: &str, user_id: &str) -> Result<(), Error> {
+        let chan_id = try!(self.evaluate_channel_id(channel));
+        channel_request("conversations.kick", &chan_id).param("user", user_id).send_ok(&self.token)
+    }
+
+    /// Wraps https://api.slack.com/methods/conversations.archive to archive a conversation.
+    pub fn conversations_archive(&self, channel: &str) -> Result<(), Error> {
+        let chan_id = try!(self.evaluate_channel_id(channel));
+        channel_request("conversations.archive", &chan_id).send_ok(&self.token)
+    }
+
+    /// Wraps https://api.slack.com/methods/conversations.create to create a public or
+    /// private channel.
+    pub fn conversations_create(&self, name: &str, is_private: bool) -> Result<Conversation, Error> {
+        let res: ConversationResponse = try!(create_request(name, is_private).send(&self.token));
+        Ok(res.channel)
+    }
+
+    /// Wraps https://api.slack.com/methods/conversations.rename to rename a conversation.
+    pub fn conversations_rename(&self, channel: &str, name: &str) -> Result<Conversation, Error> {
+        let chan_id = try!(self.evaluate_channel_id(channel));
+        let res: ConversationResponse = try!(channel_request("conversations.rename", &chan_id)
+                                                 .param("name", name)
+                                                 .send(&self.token));
+        Ok(res.channel)
+    }
+
+    /// Wraps https://api.slack.com/methods/conversations.setTopic to set the topic of a
+    /// conversation.
+    pub fn conversations_set_topic(&self, channel: &str, topic: &str) -> Result<Conversation, Error> {
+        let chan_id = try!(self.evaluate_channel_id(channel));
+        let res: ConversationResponse = try!(channel_request("conversations.setTopic", &chan_id)
+                                                 .param("topic", topic)
+                                                 .send(&self.token));
+        Ok(res.channel)
+    }
+
+    /// Wraps https://api.slack.com/methods/conversations.setPurpose to set the purpose of a
+    /// conversation.
+    pub fn conversations_set_purpose(&self, channel: &str, purpose: &str) -> Result<Conversation, Error> {
+        let chan_id = try!(self.evaluate_channel_id(channel));
+        let res: ConversationResponse = try!(channel_request("conversations.setPurpose", &chan_id)
+                                                 .param("purpose", purpose)
+                                                 .send(&self.token));
+        Ok(res.channel)
+    }
+
+    /// Wraps https://api.slack.com/methods/conversations.mark to move the read cursor in a
+    /// conversation.
+    pub fn conversations_mark(&self, channel: &str, ts: &str) -> Result<(), Error> {
+        let chan_id = try!(self.evaluate_channel_id(channel));
+        channel_request("conversations.mark", &chan_id).param("ts", ts).send_ok(&self.token)
+    }
+}
+
+/// Starts a request to a method that takes a channel id.
+fn channel_request(method: &'static str, chan_id: &str) -> Request {
+    Request::new(method).param("channel", chan_id)
+}
+
+/// Builds a request to conversations.history, or to conversations.replies which takes
+/// the same params besides the `ts` of the thread.
+fn history_request(method: &'static str,
+                   chan_id: &str,
+                   cursor: Option<&str>,
+                   latest: Option<&str>,
+                   oldest: Option<&str>,
+                   inclusive: Option<bool>,
+                   limit: Option<u32>)
+                   -> Request {
+    let limit = limit.map(|l| l.to_string());
+    channel_request(method, chan_id)
+        .opt("cursor", cursor)
+        .opt("latest", latest)
+        .opt("oldest", oldest)
+        .opt("inclusive", bool_param(inclusive))
+        .opt("limit", limit.as_ref().map(|l| &l[..]))
+}
+
+fn list_request(types: Option<&str>,
+                exclude_archived: Option<bool>,
+                cursor: Option<&str>,
+                limit: Option<u32>)
+                -> Request {
+    let limit = limit.map(|l| l.to_string());
+    Request::new("conversations.list")
+        .opt("types", types)
+        .opt("exclude_archived", bool_param(exclude_archived))
+        .opt("cursor", cursor)
+        .opt("limit", limit.as_ref().map(|l| &l[..]))
+}
+
+fn members_request(chan_id: &str, cursor: Option<&str>, limit: Option<u32>) -> Request {
+    let limit = limit.map(|l| l.to_string());
+    channel_request("conversations.members", chan_id)
+        .opt("cursor", cursor)
+        .opt("limit", limit.as_ref().map(|l| &l[..]))
+}
+
+fn create_request(name: &str, is_private: bool) -> Request {
+    Request::new("conversations.create")
+        .param("name", name)
+        .param("is_private", if is_private { "true" } else { "false" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(request: &Request) -> Vec<(&str, &str)> {
+        request.params.iter().map(|&(name, ref value)| (name, &value[..])).collect()
+    }
+
+    #[test]
+    fn resolve_channel_names() {
+        let cli = RtmClient::new("token");
+        cli.state_mut().channel_ids.insert(String::from("general"), String::from("C123"));
+        cli.state_mut().group_ids.insert(String::from("secret"), String::from("G456"));
+        assert_eq!(cli.evaluate_channel_id("#general").unwrap(), "C123");
+        assert_eq!(cli.evaluate_channel_id("#secret").unwrap(), "G456");
+        assert_eq!(cli.evaluate_channel_id("C123").unwrap(), "C123");
+        assert_eq!(cli.evaluate_channel_id("G456").unwrap(), "G456");
+        assert_eq!(cli.evaluate_channel_id("D789").unwrap(), "D789");
+        assert!(cli.evaluate_channel_id("#random").is_err());
+        assert!(cli.conversations_leave("#random").is_err());
+    }
+
+    #[test]
+    fn history_params() {
+        let request = history_request("conversations.history",
+                                      "C123",
+                                      Some("abc"),
+                                      None,
+                                      Some("1.5"),
+                                      Some(true),
+                                      Some(50));
+        assert_eq!(request.method, "conversations.history");
+        assert_eq!(params(&request),
+                   vec![("channel", "C123"),
+                        ("cursor", "abc"),
+                        ("oldest", "1.5"),
+                        ("inclusive", "true"),
+                        ("limit", "50")]);
+        let request = history_request("conversations.replies", "G1", None, None, None, None, None).param("ts", "2.0");
+        assert_eq!(params(&request), vec![("channel", "G1"), ("ts", "2.0")]);
+    }
+
+    #[test]
+    fn list_and_members_params() {
+        assert_eq!(params(&list_request(Some("im,mpim"), Some(false), None, Some(10))),
+                   vec![("types", "im,mpim"), ("exclude_archived", "false"), ("limit", "10")]);
+        assert!(list_request(None, None, None, None).params.is_empty());
+        assert_eq!(params(&members_request("D1", Some("next"), None)),
+                   vec![("channel", "D1"), ("cursor", "next")]);
+    }
+
+    #[test]
+    fn other_params() {
+        assert_eq!(params(&create_request("new-channel", true)),
+                   vec![("name", "new-channel"), ("is_private", "true")]);
+        let request = channel_request("conversations.kick", "C1").param("user", "U1");
+        assert_eq!(request.method, "conversations.kick");
+        assert_eq!(params(&request), vec![("channel", "C1"), ("user", "U1")]);
+        assert_eq!(request.with_token("xoxb-1")[0], ("token", "xoxb-1"));
+    }
+}
@@ -24,6 +24,180 @@ use websocket;
 use rustc_serialize;
 use api;
 
+/// Error codes returned by the slack Web API, see the "Errors" section of each method at
+/// https://api.slack.com/methods
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ApiErrorCode {
+    AccessDenied,
+    AccountInactive,
+    AlreadyInChannel,
+    AlreadyReacted,
+    CantDeleteMessage,
+    CantUpdateMessage,
+    ChannelNotFound,
+    EditWindowClosed,
+    FatalError,
+    FileNotFound,
+    InternalError,
+    InvalidArguments,
+    InvalidAuth,
+    InvalidBlocks,
+    InvalidPayload,
+    IsArchived,
+    MessageNotFound,
+    MissingScope,
+    MsgTooLong,
+    NameTaken,
+    NoPermission,
+    NoText,
+    NotAuthed,
+    NotInChannel,
+    Ratelimited,
+    RequestTimeout,
+    ServiceUnavailable,
+    TeamAddedToOrg,
+    TokenExpired,
+    TokenRevoked,
+    TooManyAttachments,
+    UserNotFound,
+    UsersNotFound,
+    /// Any code not listed above.
+    Other(String),
+}
+
+impl ApiErrorCode {
+    /// Returns the code as slack sends it, e.g. `channel_not_found`.
+    pub fn as_str(&self) -> &str {
+        match *self {
+            ApiErrorCode::AccessDenied => "access_denied",
+            ApiErrorCode::AccountInactive => "account_inactive",
+            ApiErrorCode::AlreadyInChannel => "already_in_channel",
+            ApiErrorCode::AlreadyReacted => "already_reacted",
+            ApiErrorCode::CantDeleteMessage => "cant_delete_message",
+            ApiErrorCode::CantUpdateMessage => "cant_update_message",
+            ApiErrorCode::ChannelNotFound => "channel_not_found",
+            ApiErrorCode::EditWindowClosed => "edit_window_closed",
+            ApiErrorCode::FatalError => "fatal_error",
+            ApiErrorCode::FileNotFound => "file_not_found",
+            ApiErrorCode::InternalError => "internal_error",
+            ApiErrorCode::InvalidArguments => "invalid_arguments",
+            ApiErrorCode::InvalidAuth => "invalid_auth",
+            ApiErrorCode::InvalidBlocks => "invalid_blocks",
+            ApiErrorCode::InvalidPayload => "invalid_payload",
+            ApiErrorCode::IsArchived => "is_archived",
+            ApiErrorCode::MessageNotFound => "message_not_found",
+            ApiErrorCode::MissingScope => "missing_scope",
+            ApiErrorCode::MsgTooLong => "msg_too_long",
+            ApiErrorCode::NameTaken => "name_taken",
+            ApiErrorCode::NoPermission => "no_permission",
+            ApiErrorCode::NoText => "no_text",
+            ApiErrorCode::NotAuthed => "not_authed",
+            ApiErrorCode::NotInChannel => "not_in_channel",
+            ApiErrorCode::Ratelimited => "ratelimited",
+            ApiErrorCode::RequestTimeout => "request_timeout",
+            ApiErrorCode::ServiceUnavailable => "service_unavailable",
+            ApiErrorCode::TeamAddedToOrg => "team_added_to_org",
+            ApiErrorCode::TokenExpired => "token_expired",
+            ApiErrorCode::TokenRevoked => "token_revoked",
+            ApiErrorCode::TooManyAttachments => "too_many_attachments",
+            ApiErrorCode::UserNotFound => "user_not_found",
+            ApiErrorCode::UsersNotFound => "users_not_found",
+            ApiErrorCode::Other(ref code) => code,
+        }
+    }
+
+    /// Maps the error of an `Event::MessageError`. RTM errors only come with a numeric code
+    /// and a description such as `message text is missing`, so the description is matched
+    /// against the Web API codes.
+    pub fn from_rtm_message(message: &str) -> ApiErrorCode {
+        let code = message.trim().to_lowercase().replace(' ', "_");
+        match &code[..] {
+            "message_text_is_missing" => ApiErrorCode::NoText,
+            "message_too_long" => ApiErrorCode::MsgTooLong,
+            "invalid_channel_id" => ApiErrorCode::ChannelNotFound,
+            "rate_limited" => ApiErrorCode::Ratelimited,
+            _ => {
+                match ApiErrorCode::from(&code[..]) {
+                    ApiErrorCode::Other(_) => ApiErrorCode::Other(message.to_string()),
+                    known => known,
+                }
+            }
+        }
+    }
+
+    /// Returns whether the same call may succeed when retried later.
+    pub fn is_retryable(&self) -> bool {
+        match *self {
+            ApiErrorCode::Ratelimited |
+            ApiErrorCode::InternalError |
+            ApiErrorCode::FatalError |
+            ApiErrorCode::RequestTimeout |
+            ApiErrorCode::ServiceUnavailable => true,
+            _ => false,
+        }
+    }
+
+    /// Returns whether the token is no longer valid, so that the app has to be authorized again.
+    pub fn is_auth_failure(&self) -> bool {
+        match *self {
+            ApiErrorCode::InvalidAuth |
+            ApiErrorCode::NotAuthed |
+            ApiErrorCode::AccountInactive |
+            ApiErrorCode::TokenExpired |
+            ApiErrorCode::TokenRevoked |
+            ApiErrorCode::TeamAddedToOrg => true,
+            _ => false,
+        }
+    }
+}
+
+impl<'a> From<&'a str> for ApiErrorCode {
+    fn from(code: &'a str) -> ApiErrorCode {
+        match code {
+            "access_denied" => ApiErrorCode::AccessDenied,
+            "account_inactive" => ApiErrorCode::AccountInactive,
+            "already_in_channel" => ApiErrorCode::AlreadyInChannel,
+            "already_reacted" => ApiErrorCode::AlreadyReacted,
+            "cant_delete_message" => ApiErrorCode::CantDeleteMessage,
+            "cant_update_message" => ApiErrorCode::CantUpdateMessage,
+            "channel_not_found" => ApiErrorCode::ChannelNotFound,
+            "edit_window_closed" => ApiErrorCode::EditWindowClosed,
+            "fatal_error" => ApiErrorCode::FatalError,
+            "file_not_found" => ApiErrorCode::FileNotFound,
+            "internal_error" => ApiErrorCode::InternalError,
+            "invalid_arguments" => ApiErrorCode::InvalidArguments,
+            "invalid_auth" => ApiErrorCode::InvalidAuth,
+            "invalid_blocks" => ApiErrorCode::InvalidBlocks,
+            "invalid_payload" => ApiErrorCode::InvalidPayload,
+            "is_archived" => ApiErrorCode::IsArchived,
+            "message_not_found" => ApiErrorCode::MessageNotFound,
+            "missing_scope" => ApiErrorCode::MissingScope,
+            "msg_too_long" => ApiErrorCode::MsgTooLong,
+            "name_taken" => ApiErrorCode::NameTaken,
+            "no_permission" => ApiErrorCode::NoPermission,
+            "no_text" => ApiErrorCode::NoText,
+            "not_authed" => ApiErrorCode::NotAuthed,
+            "not_in_channel" => ApiErrorCode::NotInChannel,
+            "ratelimited" | "rate_limited" => ApiErrorCode::Ratelimited,
+            "request_timeout" => ApiErrorCode::RequestTimeout,
+            "service_unavailable" => ApiErrorCode::ServiceUnavailable,
+            "team_added_to_org" => ApiErrorCode::TeamAddedToOrg,
+            "token_expired" => ApiErrorCode::TokenExpired,
+            "token_revoked" => ApiErrorCode::TokenRevoked,
+            "too_many_attachments" => ApiErrorCode::TooManyAttachments,
+            "user_not_found" => ApiErrorCode::UserNotFound,
+            "users_not_found" => ApiErrorCode::UsersNotFound,
+            _ => ApiErrorCode::Other(code.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for ApiErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
 /// slack::Error represents errors that can happen while using the RtmClient
 #[derive(Debug)]
 pub enum Error {
@@ -41,12 +215,93 @@ pub enum Error {
     JsonParse(rustc_serialize::json::ParserError),
     /// Error encoding Json
     JsonEncode(rustc_serialize::json::EncoderError),
-    /// Slack Api Error
-    Api(String),
-    /// Errors that do not fit under the other types, Internal is for EG channel errors.
+    /// Slack Api Error, with the HTTP status of the response when known
+    Api {
+        code: ApiErrorCode,
+        status: Option<u16>,
+    },
+    /// A request could not be verified as sent by slack
+    Signature(String),
+    /// The client has no open connection to send over, log in and run it first
+    NotConnected,
+    /// I/O error, e.g. reading a file to upload
+    Io(io::Error),
+    /// The websocket connection was closed, with the close code and reason if slack sent them
+    ConnectionClosed {
+        code: Option<u16>,
+        reason: String,
+    },
+    /// The websocket handshake with slack failed
+    HandshakeFailed(websocket::result::WebSocketError),
+    /// The thread sending messages over the websocket panicked
+    SenderThreadPanicked,
+    /// Slack did not answer in time
+    Timeout,
+    /// Errors that do not fit under the other types, e.g. unexpected payloads.
     Internal(String),
 }
 
+impl Error {
+    /// Creates an `Error::Api` from the error code of a response.
+    pub fn api(code: &str, status: Option<u16>) -> Error {
+        Error::Api {
+            code: ApiErrorCode::from(code),
+            status: status,
+        }
+    }
+
+    /// Creates an `Error::Api` from the message of an `Event::MessageError`, see
+    /// `ApiErrorCode::from_rtm_message`.
+    pub fn rtm(message: &str) -> Error {
+        Error::Api {
+            code: ApiErrorCode::from_rtm_message(message),
+            status: None,
+        }
+    }
+
+    /// Returns the Web API error code, if this is an `Error::Api`.
+    pub fn api_code(&self) -> Option<&ApiErrorCode> {
+        match *self {
+            Error::Api { ref code, .. } => Some(code),
+            _ => None,
+        }
+    }
+
+    /// Returns the HTTP status of the response that failed, when known.
+    /// Errors of the Web API methods wrapped by `slack_api` and of RTM messages never
+    /// know it, as `slack_api` doesn't return the response status.
+    pub fn status(&self) -> Option<u16> {
+        match *self {
+            Error::Api { status, .. } => status,
+            _ => None,
+        }
+    }
+
+    /// Returns whether the failed call may succeed when retried later: when slack is rate
+    /// limiting or unavailable, or the connection to it failed or was closed.
+    pub fn is_retryable(&self) -> bool {
+        match *self {
+            Error::Http(_) |
+            Error::WebSocket(_) |
+            Error::ConnectionClosed { .. } |
+            Error::HandshakeFailed(_) |
+            Error::Timeout => true,
+            Error::Api { ref code, status } => {
+                code.is_retryable() || status.map_or(false, |s| s == 429 || s >= 500)
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns whether the token is no longer valid, so that the app has to be authorized again.
+    pub fn is_auth_failure(&self) -> bool {
+        match *self {
+            Error::Api { ref code, status } => code.is_auth_failure() || status == Some(401),
+            _ => false,
+        }
+    }
+}
+
 impl From<hyper::Error> for Error {
     fn from(err: hyper::Error) -> Error {
         match err {
@@ -82,7 +337,7 @@ impl From<rustc_serialize::json::EncoderError> for Error {
 
 impl From<io::Error> for Error {
     fn from(err: io::Error) -> Error {
-        Error::Internal(format!("{:?}", err))
+        Error::Io(err)
     }
 }
 
@@ -94,7 +349,12 @@ impl From<FromUtf8Error> for Error {
 
 impl From<api::Error> for Error {
     fn from(err: api::Error) -> Error {
-        Error::Api(format!("{}", err))
+        match err {
+            api::Error::Http(err) => Error::from(err),
+            api::Error::JsonDecode(err) => Error::JsonDecode(err),
+            api::Error::Api(ref code) => Error::api(code.trim_matches('"'), None),
+            err => Error::Internal(format!("{}", err)),
+        }
     }
 }
 
@@ -108,7 +368,16 @@ impl fmt::Display for Error {
             Error::JsonDecode(ref e) => write!(f, "Json Decode Error: {:?}", e),
             Error::JsonParse(ref e) => write!(f, "Json Parse Error: {:?}", e),
             Error::JsonEncode(ref e) => write!(f, "Json Encode Error: {:?}", e),
-            Error::Api(ref st) => write!(f, "Slack Api Error: {:?}", st),
+            Error::Api { ref code, status: Some(status) } => write!(f, "Slack Api Error: {:?} (HTTP {})", code.as_str(), status),
+            Error::Api { ref code, status: None } => write!(f, "Slack Api Error: {:?}", code.as_str()),
+            Error::Signature(ref st) => write!(f, "Signature Error: {:?}", st),
+            Error::NotConnected => write!(f, "Not connected"),
+            Error::Io(ref e) => write!(f, "Io Error: {:?}", e),
+            Error::ConnectionClosed { code: Some(code), ref reason } => write!(f, "Connection closed: {} {:?}", code, reason),
+            Error::ConnectionClosed { code: None, ref reason } => write!(f, "Connection closed: {:?}", reason),
+            Error::HandshakeFailed(ref e) => write!(f, "Websocket handshake failed: {:?}", e),
+            Error::SenderThreadPanicked => write!(f, "Sender thread panicked"),
+            Error::Timeout => write!(f, "Timed out"),
             Error::Internal(ref st) => write!(f, "Internal Error: {:?}", st)
         }
     }
@@ -124,7 +393,14 @@ impl error::Error for Error {
             Error::JsonDecode(ref e) => e.description(),
             Error::JsonParse(ref e) => e.description(),
             Error::JsonEncode(ref e) => e.description(),
-            Error::Api(ref st) => st,
+            Error::Api { ref code, .. } => code.as_str(),
+            Error::Signature(ref st) => st,
+            Error::NotConnected => "not connected",
+            Error::Io(ref e) => e.description(),
+            Error::ConnectionClosed { .. } => "connection closed",
+            Error::HandshakeFailed(ref e) => e.description(),
+            Error::SenderThreadPanicked => "sender thread panicked",
+            Error::Timeout => "timed out",
             Error::Internal(ref st) => st
         }
     }
@@ -138,8 +414,67 @@ impl error::Error for Error {
             Error::JsonDecode(ref e) => Some(e),
             Error::JsonParse(ref e) => Some(e),
             Error::JsonEncode(ref e) => Some(e),
-            Error::Api(_) => None,
+            Error::Api { .. } => None,
+            Error::Signature(_) => None,
+            Error::NotConnected => None,
+            Error::Io(ref e) => Some(e),
+            Error::ConnectionClosed { .. } => None,
+            Error::HandshakeFailed(ref e) => Some(e),
+            Error::SenderThreadPanicked => None,
+            Error::Timeout => None,
             Error::Internal(_) => None
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn api_error_codes() {
+        assert_eq!(ApiErrorCode::from("channel_not_found"), ApiErrorCode::ChannelNotFound);
+        assert_eq!(ApiErrorCode::from("no_such_code"), ApiErrorCode::Other(String::from("no_such_code")));
+        assert_eq!(ApiErrorCode::MsgTooLong.as_str(), "msg_too_long");
+        assert_eq!(ApiErrorCode::from(ApiErrorCode::NotInChannel.as_str()), ApiErrorCode::NotInChannel);
+    }
+
+    #[test]
+    fn rtm_error_messages() {
+        assert_eq!(ApiErrorCode::from_rtm_message("message text is missing"), ApiErrorCode::NoText);
+        assert_eq!(ApiErrorCode::from_rtm_message("channel not found"), ApiErrorCode::ChannelNotFound);
+        assert_eq!(ApiErrorCode::from_rtm_message("Something odd"),
+                   ApiErrorCode::Other(String::from("Something odd")));
+    }
+
+    #[test]
+    fn rtm_errors() {
+        let err = Error::rtm("message text is missing");
+        assert_eq!(err.api_code(), Some(&ApiErrorCode::NoText));
+        assert_eq!(err.status(), None);
+    }
+
+    #[test]
+    fn retryable_and_auth_failures() {
+        assert!(Error::api("ratelimited", Some(429)).is_retryable());
+        assert!(Error::api("unknown", Some(503)).is_retryable());
+        assert!(!Error::api("channel_not_found", Some(200)).is_retryable());
+        assert!(Error::api("token_revoked", None).is_auth_failure());
+        assert!(!Error::api("not_in_channel", None).is_auth_failure());
+        assert_eq!(Error::api("ratelimited", Some(429)).status(), Some(429));
+    }
+
+    #[test]
+    fn from_slack_api_errors() {
+        match Error::from(api::Error::Api(String::from("invalid_auth"))) {
+            Error::Api { code: ApiErrorCode::InvalidAuth, status: None } => {}
+            err => panic!("Unexpected error: {}", err),
+        }
+        let outage = io::Error::new(io::ErrorKind::ConnectionReset, "connection reset by peer");
+        let err = Error::from(api::Error::Http(hyper::Error::Io(outage)));
+        match err {
+            Error::Http(_) => assert!(err.is_retryable()),
+            err => panic!("Unexpected error: {}", err),
+        }
+    }
+}
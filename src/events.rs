@@ -14,8 +14,10 @@
 // limitations under the License.
 //
 
-use api::{Message, Item, File, Channel, Comment, User};
+use api::{Message, File, Channel, Comment, User};
 use api::rtm::Bot;
+use error::Error;
+use mpim::Mpim;
 use rustc_serialize::{Decodable, Decoder};
 
 /// Represents Slack [rtm event](https://api.slack.com/rtm) types.
@@ -116,6 +118,23 @@ pub enum Event {
         event_ts: String,
     },
     /// Represents the slack
+    /// [`mpim_joined`](https://api.slack.com/events/mpim_joined) event.
+    MpimJoined {
+        channel: Mpim,
+    },
+    /// Represents the slack
+    /// [`mpim_open`](https://api.slack.com/events/mpim_open) event.
+    MpimOpen {
+        user: String,
+        channel: String,
+    },
+    /// Represents the slack
+    /// [`mpim_close`](https://api.slack.com/events/mpim_close) event.
+    MpimClose {
+        user: String,
+        channel: String,
+    },
+    /// Represents the slack
     /// [`group_joined`](https://api.slack.com/events/group_joined) event.
     GroupJoined {
         channel: Channel,
@@ -367,7 +386,7 @@ pub enum Event {
         ts: String,
         text: String,
     },
-    /// Represents an error sending a message
+    /// Represents an error sending a message, see `Event::error` to match on the error.
     MessageError {
         reply_to: isize,
         code: isize,
@@ -375,6 +394,162 @@ pub enum Event {
     },
 }
 
+impl Event {
+    /// Returns the error of an `Event::MessageError` as an `Error::Api`, see
+    /// `ApiErrorCode::from_rtm_message`.
+    pub fn error(&self) -> Option<Error> {
+        match *self {
+            Event::MessageError { ref message, .. } => Some(Error::rtm(message)),
+            _ => None,
+        }
+    }
+}
+
+/// An item that a reaction, pin or star refers to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Item {
+    /// A message, identified by its channel and timestamp.
+    Message {
+        channel: String,
+        ts: String,
+    },
+    /// A file, identified by its id.
+    File {
+        file: String,
+    },
+    /// A file comment, identified by its id and the id of its file when known.
+    FileComment {
+        file: Option<String>,
+        comment: String,
+    },
+    /// A channel.
+    Channel {
+        channel: String,
+    },
+    /// A direct message channel.
+    Im {
+        channel: String,
+    },
+    /// A private group.
+    Group {
+        channel: String,
+    },
+}
+
+/// The `type`s of the items that decode into an `Item`. Lists skip the items of other
+/// types, which slack may add at any time.
+pub const ITEM_TYPES: &'static [&'static str] = &["message", "file", "file_comment", "channel", "im", "group"];
+
+impl Item {
+    /// Returns the Web API params that identify this item, as taken by the
+    /// `reactions.*`, `pins.*` and `stars.*` methods.
+    pub fn api_params(&self) -> Vec<(&'static str, &str)> {
+        match *self {
+            Item::Message { ref channel, ref ts } => vec![("channel", &channel[..]), ("timestamp", &ts[..])],
+            Item::File { ref file } => vec![("file", &file[..])],
+            Item::FileComment { ref comment, .. } => vec![("file_comment", &comment[..])],
+            Item::Channel { ref channel } |
+            Item::Im { ref channel } |
+            Item::Group { ref channel } => vec![("channel", &channel[..])],
+        }
+    }
+}
+
+/// The id of an object, ignoring its other fields.
+struct IdRef(String);
+
+impl Decodable for IdRef {
+    fn decode<D: Decoder>(d: &mut D) -> Result<IdRef, D::Error> {
+        d.read_struct("IdRef", 0, |d| Ok(IdRef(try!(d.read_struct_field("id", 0, |d| Decodable::decode(d))))))
+    }
+}
+
+/// The timestamp of a message, ignoring its other fields.
+struct TsRef(String);
+
+impl Decodable for TsRef {
+    fn decode<D: Decoder>(d: &mut D) -> Result<TsRef, D::Error> {
+        d.read_struct("TsRef", 0, |d| Ok(TsRef(try!(d.read_struct_field("ts", 0, |d| Decodable::decode(d))))))
+    }
+}
+
+/// Decodes an item that includes the whole message, file or file comment, as in
+/// the pin and star events and the `pins.list` and `stars.list` methods.
+impl Decodable for Item {
+    fn decode<D: Decoder>(d: &mut D) -> Result<Item, D::Error> {
+        d.read_struct("Item", 0, |d| {
+            let ty: String = try!(d.read_struct_field("type", 0, |d| Decodable::decode(d)));
+            match ty.as_ref() {
+                "message" => {
+                    let ts: Option<String> = try!(d.read_struct_field("ts", 0, |d| Decodable::decode(d)));
+                    let ts = match ts {
+                        Some(ts) => ts,
+                        None => {
+                            let message: TsRef = try!(d.read_struct_field("message", 0, |d| Decodable::decode(d)));
+                            message.0
+                        }
+                    };
+                    Ok(Item::Message {
+                        channel: try!(d.read_struct_field("channel", 0, |d| Decodable::decode(d))),
+                        ts: ts,
+                    })
+                }
+                "file" => {
+                    let file: IdRef = try!(d.read_struct_field("file", 0, |d| Decodable::decode(d)));
+                    Ok(Item::File { file: file.0 })
+                }
+                "file_comment" => {
+                    let file: Option<IdRef> = try!(d.read_struct_field("file", 0, |d| Decodable::decode(d)));
+                    let comment: IdRef = try!(d.read_struct_field("comment", 0, |d| Decodable::decode(d)));
+                    Ok(Item::FileComment {
+                        file: file.map(|f| f.0),
+                        comment: comment.0,
+                    })
+                }
+                _ => decode_channel_item(d, ty),
+            }
+        })
+    }
+}
+
+/// Decodes an item that refers to messages, files and file comments by id,
+/// as in the reaction events.
+fn decode_item_ref<D: Decoder>(d: &mut D) -> Result<Item, D::Error> {
+    d.read_struct("Item", 0, |d| {
+        let ty: String = try!(d.read_struct_field("type", 0, |d| Decodable::decode(d)));
+        match ty.as_ref() {
+            "message" => Ok(Item::Message {
+                channel: try!(d.read_struct_field("channel", 0, |d| Decodable::decode(d))),
+                ts: try!(d.read_struct_field("ts", 0, |d| Decodable::decode(d))),
+            }),
+            "file" => Ok(Item::File {
+                file: try!(d.read_struct_field("file", 0, |d| Decodable::decode(d))),
+            }),
+            "file_comment" => Ok(Item::FileComment {
+                file: try!(d.read_struct_field("file", 0, |d| Decodable::decode(d))),
+                comment: try!(d.read_struct_field("file_comment", 0, |d| Decodable::decode(d))),
+            }),
+            _ => decode_channel_item(d, ty),
+        }
+    })
+}
+
+/// Decodes the items that are a channel, the same in every form.
+fn decode_channel_item<D: Decoder>(d: &mut D, ty: String) -> Result<Item, D::Error> {
+    match ty.as_ref() {
+        "channel" => Ok(Item::Channel {
+            channel: try!(d.read_struct_field("channel", 0, |d| Decodable::decode(d))),
+        }),
+        "im" => Ok(Item::Im {
+            channel: try!(d.read_struct_field("channel", 0, |d| Decodable::decode(d))),
+        }),
+        "group" => Ok(Item::Group {
+            channel: try!(d.read_struct_field("channel", 0, |d| Decodable::decode(d))),
+        }),
+        _ => Err(d.error(&format!("Unknown Item type: {}", ty))),
+    }
+}
+
 impl Decodable for Event {
     fn decode<D: Decoder>(d: &mut D) -> Result<Event, D::Error> {
         let ty: Option<String> = try!(d.read_struct_field("type", 0, |d| Decodable::decode(d)));
@@ -440,6 +615,17 @@ impl Decodable for Event {
                         ts: try!(d.read_struct_field("ts", 0, |d| Decodable::decode(d))),
                         event_ts: try!(d.read_struct_field("event_ts", 0, |d| Decodable::decode(d))),
                     }),
+                    "mpim_joined" => Ok(Event::MpimJoined {
+                        channel: try!(d.read_struct_field("channel", 0, |d| Decodable::decode(d))),
+                    }),
+                    "mpim_open" => Ok(Event::MpimOpen {
+                        user: try!(d.read_struct_field("user", 0, |d| Decodable::decode(d))),
+                        channel: try!(d.read_struct_field("channel", 0, |d| Decodable::decode(d))),
+                    }),
+                    "mpim_close" => Ok(Event::MpimClose {
+                        user: try!(d.read_struct_field("user", 0, |d| Decodable::decode(d))),
+                        channel: try!(d.read_struct_field("channel", 0, |d| Decodable::decode(d))),
+                    }),
                     "group_joined" => Ok(Event::GroupJoined {
                         channel: try!(d.read_struct_field("channel", 0, |d| Decodable::decode(d))),
                     }),
@@ -549,14 +735,14 @@ impl Decodable for Event {
                     "reaction_added" => Ok(Event::ReactionAdded {
                         user: try!(d.read_struct_field("user", 0, |d| Decodable::decode(d))),
                         reaction: try!(d.read_struct_field("name", 0, |d| Decodable::decode(d))),
-                        item: try!(d.read_struct_field("item", 0, |d| Decodable::decode(d))),
+                        item: try!(d.read_struct_field("item", 0, |d| decode_item_ref(d))),
                         item_user: try!(d.read_struct_field("item_user", 0, |d| Decodable::decode(d))),
                         event_ts: try!(d.read_struct_field("event_ts", 0, |d| Decodable::decode(d))),
                     }),
                     "reaction_removed" => Ok(Event::ReactionRemoved {
                         user: try!(d.read_struct_field("user", 0, |d| Decodable::decode(d))),
                         reaction: try!(d.read_struct_field("name", 0, |d| Decodable::decode(d))),
-                        item: try!(d.read_struct_field("item", 0, |d| Decodable::decode(d))),
+                        item: try!(d.read_struct_field("item", 0, |d| decode_item_ref(d))),
                         item_user: try!(d.read_struct_field("item_user", 0, |d| Decodable::decode(d))),
                         event_ts: try!(d.read_struct_field("event_ts", 0, |d| Decodable::decode(d))),
                       }),
@@ -628,6 +814,7 @@ impl Decodable for Event {
 mod tests {
     use super::*;
     use api::Message;
+    use error::ApiErrorCode;
     use rustc_serialize::json;
 
     #[test]
@@ -684,13 +871,91 @@ mod tests {
             }
         }"#).unwrap();
         match event {
-            Event::MessageError{reply_to, code, message} => {
+            Event::MessageError{reply_to, code, ref message} => {
                 assert_eq!(reply_to, 1);
                 assert_eq!(code, 2);
                 assert_eq!(message, "message text is missing");
             },
             _ => panic!("Event decoded into incorrect variant."),
         }
+        assert_eq!(event.error().unwrap().api_code(), Some(&ApiErrorCode::NoText));
+    }
+
+    #[test]
+    fn decode_mpim_joined() {
+        let event: Event = json::decode(r#"{
+            "type": "mpim_joined",
+            "channel": {
+                "id": "G12345678",
+                "name": "mpdm-alice--bob--carol-1",
+                "is_mpim": true,
+                "created": 1360782804,
+                "creator": "U12345678",
+                "members": [ "U12345678", "U87654321", "U11111111" ]
+            }
+        }"#).unwrap();
+        match event {
+            Event::MpimJoined{channel} => {
+                assert_eq!(channel.id, "G12345678");
+                assert_eq!(channel.members.unwrap().len(), 3);
+            },
+            _ => panic!("Event decoded into incorrect variant."),
+        }
+    }
+
+    #[test]
+    fn decode_reaction_added() {
+        let event: Event = json::decode(r#"{
+            "type": "reaction_added",
+            "user": "U12345678",
+            "reaction": "thumbsup",
+            "name": "thumbsup",
+            "item_user": "U87654321",
+            "item": {
+                "type": "message",
+                "channel": "C12345678",
+                "ts": "1360782400.498405"
+            },
+            "event_ts": "1360782804.083113"
+        }"#).unwrap();
+        match event {
+            Event::ReactionAdded{reaction, item, ..} => {
+                assert_eq!(reaction, "thumbsup");
+                assert_eq!(item, Item::Message {
+                    channel: String::from("C12345678"),
+                    ts: String::from("1360782400.498405"),
+                });
+            },
+            _ => panic!("Event decoded into incorrect variant."),
+        }
+    }
+
+    #[test]
+    fn decode_star_added() {
+        let event: Event = json::decode(r#"{
+            "type": "star_added",
+            "user": "U12345678",
+            "item": {
+                "type": "message",
+                "channel": "C12345678",
+                "message": {
+                    "type": "message",
+                    "user": "U87654321",
+                    "text": "Hello world",
+                    "ts": "1360782400.498405"
+                }
+            },
+            "event_ts": "1360782804.083113"
+        }"#).unwrap();
+        match event {
+            Event::StarAdded{item, ..} => {
+                assert_eq!(item, Item::Message {
+                    channel: String::from("C12345678"),
+                    ts: String::from("1360782400.498405"),
+                });
+            },
+            _ => panic!("Event decoded into incorrect variant."),
+        }
     }
 
     #[test]
@@ -0,0 +1,316 @@
+//
+// Copyright 2014-2016 the slack-rs authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Receiving events over HTTP with the Events API, see https://api.slack.com/apis/connections/events-api
+//!
+//! Events are decoded into the same `Event` enum and passed to the same `EventHandler` as
+//! with the RTM API, so a bot can switch transports without changing its handler.
+//! `on_connect` is called once the server is listening; `on_ping` and `on_close` are
+//! never called, as there is no connection to keep alive.
+//!
+//! Slack expects a request to be answered within 3 seconds and otherwise retries it, with
+//! the number of the retry in the `X-Slack-Retry-Num` header. `EventsApiServer` answers
+//! before passing the event to the handler, and retries are answered and ignored, so a
+//! slow handler doesn't receive the same event several times.
+
+use std::io::Read;
+use std::net::ToSocketAddrs;
+use std::sync::Mutex;
+use std::str;
+
+use hyper::server::{Handler, Listening, Request, Response, Server};
+use hyper::status::StatusCode;
+use rustc_serialize::json::{self, Json};
+
+use error::Error;
+use events::Event;
+use signature;
+use EventHandler;
+use RtmClient;
+
+/// The header slack sets on the retries of a request that wasn't answered in time.
+pub const RETRY_HEADER: &'static str = "X-Slack-Retry-Num";
+
+/// The HTTP response to send back to slack for a request.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+impl HttpResponse {
+    fn new(status: u16, body: &str) -> HttpResponse {
+        HttpResponse {
+            status: status,
+            body: body.to_string(),
+        }
+    }
+}
+
+/// Handles an Events API request independently of the HTTP server it came from.
+///
+/// The request is verified with signing_secret against the values of the
+/// `X-Slack-Request-Timestamp` and `X-Slack-Signature` headers and the raw body.
+/// `url_verification` challenges are answered, and the event of an `event_callback`
+/// is passed to handler unless retry_num, the value of the `X-Slack-Retry-Num` header,
+/// is set. Other payload types are acknowledged and ignored.
+///
+/// The handler is called before this returns, so it has to finish well within the 3
+/// seconds slack waits for an answer; `EventsApiServer` answers first instead.
+pub fn handle_request<T: EventHandler>(cli: &mut RtmClient,
+                                       handler: &mut T,
+                                       signing_secret: &str,
+                                       timestamp: Option<&str>,
+                                       signature: Option<&str>,
+                                       retry_num: Option<&str>,
+                                       body: &[u8])
+                                       -> HttpResponse {
+    let (response, event) = acknowledge(signing_secret, timestamp, signature, retry_num, body);
+    if let Some((event, raw_json)) = event {
+        handler.on_event(cli, event, &raw_json);
+    }
+    response
+}
+
+/// Returns the response to a request, see `handle_request`, along with the event to pass
+/// to the handler and its raw json once the response has been sent.
+fn acknowledge(signing_secret: &str,
+               timestamp: Option<&str>,
+               signature: Option<&str>,
+               retry_num: Option<&str>,
+               body: &[u8])
+               -> (HttpResponse, Option<(Result<Event, Error>, String)>) {
+    let verified = match (timestamp, signature) {
+        (Some(timestamp), Some(signature)) => signature::verify(signing_secret, timestamp, signature, body),
+        _ => Err(Error::Signature(String::from("missing signature headers"))),
+    };
+    if verified.is_err() {
+        return (HttpResponse::new(401, "invalid signature"), None);
+    }
+
+    let payload = match str::from_utf8(body).ok().and_then(|body| Json::from_str(body).ok()) {
+        Some(payload) => payload,
+        None => return (HttpResponse::new(400, "invalid payload"), None),
+    };
+
+    match payload.find("type").and_then(|t| t.as_string()) {
+        Some("url_verification") => {
+            match payload.find("challenge").and_then(|c| c.as_string()) {
+                Some(challenge) => (HttpResponse::new(200, challenge), None),
+                None => (HttpResponse::new(400, "missing challenge"), None),
+            }
+        }
+        Some("event_callback") => {
+            match payload.find("event") {
+                // the first delivery of the event was already answered and dispatched
+                Some(_) if retry_num.is_some() => (HttpResponse::new(200, ""), None),
+                Some(event) => {
+                    let raw_json = event.to_string();
+                    let event = json::decode::<Event>(&raw_json).map_err(|e| e.into());
+                    (HttpResponse::new(200, ""), Some((event, raw_json)))
+                }
+                None => (HttpResponse::new(400, "missing event"), None),
+            }
+        }
+        _ => (HttpResponse::new(200, ""), None),
+    }
+}
+
+/// A hyper request handler for the Events API, passing events to an `EventHandler`.
+pub struct EventsApiServer<T: EventHandler + Send> {
+    signing_secret: String,
+    cli: Mutex<RtmClient>,
+    handler: Mutex<T>,
+}
+
+impl<T: EventHandler + Send> EventsApiServer<T> {
+    /// Creates a server that verifies requests with signing_secret and passes events to
+    /// handler along with cli, which is used for Web API calls.
+    pub fn new(signing_secret: &str, cli: RtmClient, handler: T) -> EventsApiServer<T> {
+        EventsApiServer {
+            signing_secret: signing_secret.to_string(),
+            cli: Mutex::new(cli),
+            handler: Mutex::new(handler),
+        }
+    }
+
+    /// Handles a request, see `handle_request`.
+    pub fn handle_request(&self,
+                          timestamp: Option<&str>,
+                          signature: Option<&str>,
+                          retry_num: Option<&str>,
+                          body: &[u8])
+                          -> HttpResponse {
+        let (response, event) = acknowledge(&self.signing_secret, timestamp, signature, retry_num, body);
+        if let Some((event, raw_json)) = event {
+            self.dispatch(event, &raw_json);
+        }
+        response
+    }
+
+    /// Passes an event to the handler.
+    fn dispatch(&self, event: Result<Event, Error>, raw_json: &str) {
+        // a handler that panicked leaves the client and handler usable, so ignore poisoning
+        let mut cli = self.cli.lock().unwrap_or_else(|e| e.into_inner());
+        let mut handler = self.handler.lock().unwrap_or_else(|e| e.into_inner());
+        handler.on_event(&mut cli, event, raw_json);
+    }
+
+    /// Starts listening for requests on addr in the background.
+    /// Every request path is treated as the Events API request url. Requests are answered
+    /// before their event is passed to the handler, on the thread of the request.
+    pub fn listen<A: ToSocketAddrs>(self, addr: A) -> Result<Listening, Error>
+        where T: 'static
+    {
+        {
+            let mut cli = self.cli.lock().unwrap_or_else(|e| e.into_inner());
+            let mut handler = self.handler.lock().unwrap_or_else(|e| e.into_inner());
+            handler.on_connect(&mut cli);
+        }
+        let server = try!(Server::http(addr));
+        Ok(try!(server.handle(self)))
+    }
+}
+
+/// Returns the first value of a header as a string.
+fn header<'a>(req: &'a Request, name: &str) -> Option<&'a str> {
+    req.headers
+       .get_raw(name)
+       .and_then(|values| values.first())
+       .and_then(|value| str::from_utf8(value).ok())
+}
+
+impl<T: EventHandler + Send> Handler for EventsApiServer<T> {
+    fn handle(&self, mut req: Request, mut res: Response) {
+        let mut body = Vec::new();
+        let (response, event) = match req.read_to_end(&mut body) {
+            Ok(_) => {
+                let timestamp = header(&req, signature::TIMESTAMP_HEADER).map(|t| t.to_string());
+                let signature = header(&req, signature::SIGNATURE_HEADER).map(|s| s.to_string());
+                let retry_num = header(&req, RETRY_HEADER).map(|r| r.to_string());
+                acknowledge(&self.signing_secret,
+                            timestamp.as_ref().map(|t| &t[..]),
+                            signature.as_ref().map(|s| &s[..]),
+                            retry_num.as_ref().map(|r| &r[..]),
+                            &body)
+            }
+            Err(_) => (HttpResponse::new(400, "unreadable body"), None),
+        };
+        *res.status_mut() = StatusCode::from_u16(response.status);
+        // slack retries the request if it can't be answered, nothing else to do here
+        let _ = res.send(response.body.as_bytes());
+        // answer before calling the handler, which may take longer than slack waits
+        if let Some((event, raw_json)) = event {
+            self.dispatch(event, &raw_json);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use super::*;
+    use error::Error;
+    use events::Event;
+    use signature;
+    use EventHandler;
+    use RtmClient;
+
+    const SECRET: &'static str = "secret";
+
+    struct Recorder {
+        events: Vec<Result<Event, Error>>,
+    }
+
+    impl EventHandler for Recorder {
+        fn on_event(&mut self, _: &mut RtmClient, event: Result<Event, Error>, _: &str) {
+            self.events.push(event);
+        }
+        fn on_ping(&mut self, _: &mut RtmClient) {}
+        fn on_close(&mut self, _: &mut RtmClient, _: Option<u16>, _: &str) {}
+        fn on_connect(&mut self, _: &mut RtmClient) {}
+    }
+
+    fn send(body: &str, secret: &str) -> (HttpResponse, Recorder) {
+        send_retry(body, secret, None)
+    }
+
+    fn send_retry(body: &str, secret: &str, retry_num: Option<&str>) -> (HttpResponse, Recorder) {
+        let mut cli = RtmClient::new("xoxb-test");
+        let mut handler = Recorder { events: Vec::new() };
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs().to_string();
+        let sig = signature::sign(secret, &now, body.as_bytes());
+        let res = handle_request(&mut cli,
+                                 &mut handler,
+                                 SECRET,
+                                 Some(&now),
+                                 Some(&sig),
+                                 retry_num,
+                                 body.as_bytes());
+        (res, handler)
+    }
+
+    const PREF_CHANGE: &'static str = r#"{
+        "type": "event_callback",
+        "team_id": "T12345678",
+        "event": {"type": "pref_change", "name": "messages_theme", "value": "dense"}
+    }"#;
+
+    #[test]
+    fn answers_url_verification() {
+        let (res, handler) = send(r#"{"type": "url_verification", "challenge": "abc123"}"#, SECRET);
+        assert_eq!(res, HttpResponse::new(200, "abc123"));
+        assert!(handler.events.is_empty());
+    }
+
+    #[test]
+    fn dispatches_event_callbacks() {
+        let (res, handler) = send(PREF_CHANGE, SECRET);
+        assert_eq!(res.status, 200);
+        assert_eq!(handler.events.len(), 1);
+        match handler.events[0] {
+            Ok(Event::PrefChange { ref name, .. }) => assert_eq!(name, "messages_theme"),
+            _ => panic!("Event was not dispatched."),
+        }
+    }
+
+    #[test]
+    fn ignores_retries() {
+        let (res, handler) = send_retry(PREF_CHANGE, SECRET, Some("1"));
+        assert_eq!(res, HttpResponse::new(200, ""));
+        assert!(handler.events.is_empty());
+    }
+
+    #[test]
+    fn acknowledges_before_dispatching() {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs().to_string();
+        let sig = signature::sign(SECRET, &now, PREF_CHANGE.as_bytes());
+        let (res, event) = acknowledge(SECRET, Some(&now), Some(&sig), None, PREF_CHANGE.as_bytes());
+        assert_eq!(res, HttpResponse::new(200, ""));
+        match event {
+            Some((Ok(Event::PrefChange { ref name, .. }), _)) => assert_eq!(name, "messages_theme"),
+            _ => panic!("Event was not returned for dispatching."),
+        }
+    }
+
+    #[test]
+    fn rejects_bad_signatures() {
+        let (res, handler) = send(r#"{"type": "url_verification", "challenge": "abc123"}"#, "other");
+        assert_eq!(res.status, 401);
+        assert!(handler.events.is_empty());
+    }
+}
@@ -0,0 +1,370 @@
+//
+// Copyright 2014-2016 the slack-rs authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Uploading, downloading and managing files, see https://api.slack.com/types/file
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hyper;
+use hyper::header::{Authorization, Bearer, ContentType};
+
+use api::{Comment, File};
+use error::Error;
+use web;
+use RtmClient;
+
+/// Response of https://api.slack.com/methods/files.info
+#[derive(Clone, Debug, RustcDecodable)]
+pub struct InfoResponse {
+    pub file: File,
+    pub comments: Option<Vec<Comment>>,
+}
+
+/// Paging information returned by list methods.
+#[derive(Clone, Debug, RustcDecodable)]
+pub struct Paging {
+    pub count: u32,
+    pub total: u32,
+    pub page: u32,
+    pub pages: u32,
+}
+
+/// Response of https://api.slack.com/methods/files.list
+#[derive(Clone, Debug, RustcDecodable)]
+pub struct ListResponse {
+    pub files: Vec<File>,
+    pub paging: Option<Paging>,
+}
+
+#[derive(RustcDecodable)]
+struct FileResponse {
+    file: File,
+}
+
+/// Where the contents of an upload come from.
+enum Content<'a> {
+    Bytes(Vec<u8>),
+    Reader(Box<Read + 'a>),
+    Snippet(String),
+}
+
+/// A file to upload with `RtmClient::upload_file`.
+///
+/// ```no_run
+/// # let cli = slack::RtmClient::new("token");
+/// let upload = slack::files::FileUpload::snippet("build log")
+///                  .channels(&["#ci"])
+///                  .title("Build #42");
+/// let file = cli.upload_file(upload);
+/// ```
+pub struct FileUpload<'a> {
+    content: Content<'a>,
+    filename: Option<String>,
+    filetype: Option<String>,
+    title: Option<String>,
+    initial_comment: Option<String>,
+    channels: Vec<String>,
+    thread_ts: Option<String>,
+}
+
+impl<'a> FileUpload<'a> {
+    fn new(content: Content<'a>, filename: Option<String>) -> FileUpload<'a> {
+        FileUpload {
+            content: content,
+            filename: filename,
+            filetype: None,
+            title: None,
+            initial_comment: None,
+            channels: Vec::new(),
+            thread_ts: None,
+        }
+    }
+
+    /// Uploads the file at path, using its file name.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<FileUpload<'a>, Error> {
+        let path = path.as_ref();
+        let mut bytes = Vec::new();
+        try!(try!(fs::File::open(path)).read_to_end(&mut bytes));
+        let filename = path.file_name().map(|name| name.to_string_lossy().into_owned());
+        Ok(FileUpload::new(Content::Bytes(bytes), filename))
+    }
+
+    /// Uploads bytes as a file called filename.
+    pub fn from_bytes(filename: &str, bytes: Vec<u8>) -> FileUpload<'a> {
+        FileUpload::new(Content::Bytes(bytes), Some(filename.to_string()))
+    }
+
+    /// Uploads everything read from reader as a file called filename.
+    /// The reader is consumed when the file is uploaded.
+    pub fn from_reader<R: Read + 'a>(filename: &str, reader: R) -> FileUpload<'a> {
+        FileUpload::new(Content::Reader(Box::new(reader)), Some(filename.to_string()))
+    }
+
+    /// Creates a text snippet rather than a binary file.
+    pub fn snippet(content: &str) -> FileUpload<'a> {
+        FileUpload::new(Content::Snippet(content.to_string()), None)
+    }
+
+    /// Sets the file name shown in slack.
+    pub fn filename(mut self, filename: &str) -> FileUpload<'a> {
+        self.filename = Some(filename.to_string());
+        self
+    }
+
+    /// Sets the file type, see https://api.slack.com/types/file#file_types
+    pub fn filetype(mut self, filetype: &str) -> FileUpload<'a> {
+        self.filetype = Some(filetype.to_string());
+        self
+    }
+
+    /// Sets the title of the file.
+    pub fn title(mut self, title: &str) -> FileUpload<'a> {
+        self.title = Some(title.to_string());
+        self
+    }
+
+    /// Sets a message to post along with the file.
+    pub fn initial_comment(mut self, comment: &str) -> FileUpload<'a> {
+        self.initial_comment = Some(comment.to_string());
+        self
+    }
+
+    /// Shares the file in the channels, which can be ids or `#names`.
+    pub fn channels(mut self, channels: &[&str]) -> FileUpload<'a> {
+        self.channels.extend(channels.iter().map(|c| c.to_string()));
+        self
+    }
+
+    /// Shares the file as a reply in the thread with the given parent message timestamp.
+    pub fn thread_ts(mut self, ts: &str) -> FileUpload<'a> {
+        self.thread_ts = Some(ts.to_string());
+        self
+    }
+}
+
+/// Builds a multipart/form-data request body.
+struct Multipart {
+    boundary: String,
+    body: Vec<u8>,
+}
+
+impl Multipart {
+    fn new() -> Multipart {
+        let nanos = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map(|d| d.subsec_nanos() as u64 ^ d.as_secs())
+                        .unwrap_or(0);
+        Multipart {
+            boundary: format!("slack-rs-{:016x}", nanos),
+            body: Vec::new(),
+        }
+    }
+
+    fn text(&mut self, name: &str, value: &str) {
+        self.body.extend_from_slice(format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n",
+                                            self.boundary,
+                                            name)
+                                        .as_bytes());
+        self.body.extend_from_slice(value.as_bytes());
+        self.body.extend_from_slice(b"\r\n");
+    }
+
+    fn file(&mut self, name: &str, filename: &str, contents: &[u8]) {
+        self.body.extend_from_slice(format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"; \
+                                             filename=\"{}\"\r\nContent-Type: \
+                                             application/octet-stream\r\n\r\n",
+                                            self.boundary,
+                                            name,
+                                            filename.replace('"', "'"))
+                                        .as_bytes());
+        self.body.extend_from_slice(contents);
+        self.body.extend_from_slice(b"\r\n");
+    }
+
+    fn finish(mut self) -> (String, Vec<u8>) {
+        let end = format!("--{}--\r\n", self.boundary);
+        self.body.extend_from_slice(end.as_bytes());
+        (format!("multipart/form-data; boundary={}", self.boundary), self.body)
+    }
+}
+
+/// Returns the error for a failed file download, which has no error code of its own,
+/// so that `Error::status`, `Error::is_retryable` and `Error::is_auth_failure` work.
+fn download_error(status: u16) -> Error {
+    let code = match status {
+        401 => "not_authed",
+        403 => "invalid_auth",
+        404 => "file_not_found",
+        429 => "ratelimited",
+        _ if status >= 500 => "service_unavailable",
+        _ => "unknown_error",
+    };
+    Error::api(code, Some(status))
+}
+
+impl RtmClient {
+    /// Wraps https://api.slack.com/methods/files.upload to upload a file or create a snippet.
+    pub fn upload_file(&self, upload: FileUpload) -> Result<File, Error> {
+        let mut channels = Vec::new();
+        for chan in upload.channels.iter() {
+            channels.push(try!(self.evaluate_channel_id(chan)));
+        }
+        let channels = channels.join(",");
+
+        let mut form = Multipart::new();
+        form.text("token", &self.token[..]);
+        if !channels.is_empty() {
+            form.text("channels", &channels);
+        }
+        let fields = [("filename", &upload.filename),
+                      ("filetype", &upload.filetype),
+                      ("title", &upload.title),
+                      ("initial_comment", &upload.initial_comment),
+                      ("thread_ts", &upload.thread_ts)];
+        for &(name, value) in fields.iter() {
+            if let Some(ref value) = *value {
+                form.text(name, value);
+            }
+        }
+        let filename = upload.filename.clone().unwrap_or_else(|| String::from("file"));
+        match upload.content {
+            Content::Bytes(bytes) => form.file("file", &filename, &bytes),
+            Content::Reader(mut reader) => {
+                let mut bytes = Vec::new();
+                try!(reader.read_to_end(&mut bytes));
+                form.file("file", &filename, &bytes);
+            }
+            Content::Snippet(content) => form.text("content", &content),
+        }
+        let (content_type, body) = form.finish();
+        let content_type = try!(content_type.parse()
+                                            .map_err(|_| Error::Internal(String::from("invalid multipart content type"))));
+
+        let client = hyper::Client::new();
+        let res = try!(client.post(&format!("{}files.upload", web::API_BASE))
+                             .header(ContentType(content_type))
+                             .body(&body[..])
+                             .send());
+        let json = try!(web::read_response(res));
+        let res: FileResponse = try!(web::decode(json));
+        Ok(res.file)
+    }
+
+    /// Downloads the contents of a file's `url_private` (or `url_private_download`) into writer,
+    /// authenticating with the client's token. Returns the number of bytes written.
+    pub fn download_file<W: Write>(&self, url_private: &str, writer: &mut W) -> Result<u64, Error> {
+        let client = hyper::Client::new();
+        let mut res = try!(client.get(url_private)
+                                 .header(Authorization(Bearer { token: self.token.clone() }))
+                                 .send());
+        if !res.status.is_success() {
+            return Err(download_error(res.status.to_u16()));
+        }
+        Ok(try!(io::copy(&mut res, writer)))
+    }
+
+    /// Wraps https://api.slack.com/methods/files.info to get information about a file.
+    pub fn file_info(&self, file_id: &str) -> Result<InfoResponse, Error> {
+        let client = hyper::Client::new();
+        web::call(&client, "files.info", &[("token", &self.token[..]), ("file", file_id)])
+    }
+
+    /// Wraps https://api.slack.com/methods/files.list to list files, optionally filtered by
+    /// user, channel, creation time (ts_from, ts_to) and a comma separated list of types.
+    pub fn list_files(&self,
+                      user: Option<&str>,
+                      channel: Option<&str>,
+                      ts_from: Option<&str>,
+                      ts_to: Option<&str>,
+                      types: Option<&str>,
+                      count: Option<u32>,
+                      page: Option<u32>)
+                      -> Result<ListResponse, Error> {
+        let channel = match channel {
+            Some(chan) => Some(try!(self.evaluate_channel_id(chan))),
+            None => None,
+        };
+        let count = count.map(|c| c.to_string());
+        let page = page.map(|p| p.to_string());
+        let mut params = vec![("token", &self.token[..])];
+        web::push_opt(&mut params, "user", user);
+        web::push_opt(&mut params, "channel", channel.as_ref().map(|c| &c[..]));
+        web::push_opt(&mut params, "ts_from", ts_from);
+        web::push_opt(&mut params, "ts_to", ts_to);
+        web::push_opt(&mut params, "types", types);
+        web::push_opt(&mut params, "count", count.as_ref().map(|c| &c[..]));
+        web::push_opt(&mut params, "page", page.as_ref().map(|p| &p[..]));
+
+        let client = hyper::Client::new();
+        web::call(&client, "files.list", &params)
+    }
+
+    /// Wraps https://api.slack.com/methods/files.delete to delete a file.
+    pub fn delete_file(&self, file_id: &str) -> Result<(), Error> {
+        let client = hyper::Client::new();
+        try!(web::call_json(&client, "files.delete", &[("token", &self.token[..]), ("file", file_id)]));
+        Ok(())
+    }
+
+    /// Wraps https://api.slack.com/methods/files.sharedPublicURL to enable public sharing
+    /// of a file. The returned file has its `permalink_public` set.
+    pub fn shared_public_url(&self, file_id: &str) -> Result<File, Error> {
+        let client = hyper::Client::new();
+        let res: FileResponse = try!(web::call(&client,
+                                               "files.sharedPublicURL",
+                                               &[("token", &self.token[..]), ("file", file_id)]));
+        Ok(res.file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multipart_body() {
+        let mut form = Multipart {
+            boundary: String::from("slack-rs-boundary"),
+            body: Vec::new(),
+        };
+        form.text("channels", "C123,C456");
+        form.file("file", "my \"log\".txt", b"line 1\nline 2");
+        let (content_type, body) = form.finish();
+        assert_eq!(content_type, "multipart/form-data; boundary=slack-rs-boundary");
+        assert_eq!(String::from_utf8(body).unwrap(),
+                   "--slack-rs-boundary\r\n\
+                    Content-Disposition: form-data; name=\"channels\"\r\n\r\n\
+                    C123,C456\r\n\
+                    --slack-rs-boundary\r\n\
+                    Content-Disposition: form-data; name=\"file\"; filename=\"my 'log'.txt\"\r\n\
+                    Content-Type: application/octet-stream\r\n\r\n\
+                    line 1\nline 2\r\n\
+                    --slack-rs-boundary--\r\n");
+    }
+
+    #[test]
+    fn download_errors() {
+        assert!(download_error(401).is_auth_failure());
+        assert!(download_error(403).is_auth_failure());
+        assert_eq!(download_error(403).status(), Some(403));
+        assert!(!download_error(404).is_auth_failure());
+        assert!(download_error(503).is_retryable());
+        assert!(!download_error(404).is_retryable());
+    }
+}
@@ -0,0 +1,811 @@
+//
+// Copyright 2014-2016 the slack-rs authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Helpers for Slack's [message formatting](https://api.slack.com/docs/message-formatting).
+//!
+//! Slack reserves `&`, `<` and `>` in message text, so any text that did not
+//! come from Slack itself should be passed through `escape` before it is sent.
+//! The remaining functions build the control sequences for mentions, links and
+//! dates, and `parse` splits incoming message text back into tokens.
+
+use std::cmp;
+use std::fmt;
+use std::mem;
+
+/// The longest message text accepted over the rtm websocket.
+pub const RTM_MESSAGE_LIMIT: usize = 4000;
+
+/// The longest message text accepted by `chat.postMessage`.
+pub const WEB_MESSAGE_LIMIT: usize = 40000;
+
+/// Escapes the control characters `&`, `<` and `>` in text sent to slack.
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Reverses `escape`, turning `&amp;`, `&lt;` and `&gt;` back into their characters.
+pub fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// Formats a mention of the user with the given id, e.g. `<@U12345678>`.
+pub fn user_mention(user_id: &str) -> String {
+    format!("<@{}>", user_id)
+}
+
+/// Formats a link to the channel with the given id, e.g. `<#C12345678>`.
+pub fn channel_mention(channel_id: &str) -> String {
+    format!("<#{}>", channel_id)
+}
+
+/// Formats a mention of the user group with the given id, e.g. `<!subteam^S12345678>`.
+pub fn usergroup_mention(usergroup_id: &str) -> String {
+    format!("<!subteam^{}>", usergroup_id)
+}
+
+/// Formats an `@here` mention, notifying the active members of a channel.
+pub fn here() -> String {
+    String::from("<!here>")
+}
+
+/// Formats an `@channel` mention, notifying all members of a channel.
+pub fn channel() -> String {
+    String::from("<!channel>")
+}
+
+/// Formats an `@everyone` mention, notifying every member of the team.
+pub fn everyone() -> String {
+    String::from("<!everyone>")
+}
+
+/// Formats a link to url, optionally displayed as label.
+pub fn link(url: &str, label: Option<&str>) -> String {
+    match label {
+        Some(label) => format!("<{}|{}>", escape(url), escape(label)),
+        None => format!("<{}>", escape(url)),
+    }
+}
+
+/// Formats a date token that each client renders in its own timezone.
+/// See https://api.slack.com/docs/message-formatting#formatting_dates for the
+/// tokens allowed in `format`, fallback is shown by clients that can't render dates.
+pub fn date(timestamp: i64, format: &str, link: Option<&str>, fallback: &str) -> String {
+    match link {
+        Some(link) => format!("<!date^{}^{}^{}|{}>", timestamp, format, link, escape(fallback)),
+        None => format!("<!date^{}^{}|{}>", timestamp, format, escape(fallback)),
+    }
+}
+
+/// A piece of slack message text, as returned by `parse`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    /// Plain (unescaped) text.
+    Text(String),
+    /// A user mention, `<@U12345678|bob>`.
+    User {
+        id: String,
+        label: Option<String>,
+    },
+    /// A channel link, `<#C12345678|general>`.
+    Channel {
+        id: String,
+        label: Option<String>,
+    },
+    /// A user group mention, `<!subteam^S12345678|@team>`.
+    UserGroup {
+        id: String,
+        label: Option<String>,
+    },
+    /// A special mention such as `<!here>`, `<!channel>` or `<!everyone>`.
+    Special {
+        name: String,
+        label: Option<String>,
+    },
+    /// A date, `<!date^1392734382^{date_short}|Feb 18, 2014>`.
+    Date {
+        timestamp: i64,
+        format: String,
+        link: Option<String>,
+        fallback: String,
+    },
+    /// A link, `<https://example.com|example>`.
+    Link {
+        url: String,
+        label: Option<String>,
+    },
+}
+
+impl fmt::Display for Token {
+    /// Formats the token back into slack message text.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn with_label(f: &mut fmt::Formatter, target: &str, label: &Option<String>) -> fmt::Result {
+            match *label {
+                Some(ref label) => write!(f, "<{}|{}>", target, escape(label)),
+                None => write!(f, "<{}>", target),
+            }
+        }
+
+        match *self {
+            Token::Text(ref text) => write!(f, "{}", escape(text)),
+            Token::User { ref id, ref label } => with_label(f, &format!("@{}", id), label),
+            Token::Channel { ref id, ref label } => with_label(f, &format!("#{}", id), label),
+            Token::UserGroup { ref id, ref label } => with_label(f, &format!("!subteam^{}", id), label),
+            Token::Special { ref name, ref label } => with_label(f, &format!("!{}", name), label),
+            Token::Date { timestamp, ref format, ref link, ref fallback } => {
+                write!(f, "{}", date(timestamp, format, link.as_ref().map(|s| &s[..]), fallback))
+            }
+            Token::Link { ref url, ref label } => with_label(f, &escape(url), label),
+        }
+    }
+}
+
+/// Splits slack message text, such as the `text` of a `Message::Standard`, into
+/// plain text and the mentions and links embedded in it.
+/// Plain text and labels are unescaped.
+pub fn parse(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut plain = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find('<') {
+        let end = match rest[start..].find('>') {
+            Some(end) => start + end,
+            None => break,
+        };
+        plain.push_str(&rest[..start]);
+        match parse_control(&rest[start + 1..end]) {
+            Some(token) => {
+                if !plain.is_empty() {
+                    tokens.push(Token::Text(unescape(&plain)));
+                    plain.clear();
+                }
+                tokens.push(token);
+            }
+            None => plain.push_str(&rest[start..end + 1]),
+        }
+        rest = &rest[end + 1..];
+    }
+    plain.push_str(rest);
+    if !plain.is_empty() {
+        tokens.push(Token::Text(unescape(&plain)));
+    }
+
+    tokens
+}
+
+/// Splits message text into chunks of at most limit characters.
+/// Text is split at line breaks where possible, then at whitespace, and only
+/// cut mid-word when a single word is longer than limit. Mentions and links are
+/// not split at their whitespace, but like any other word they are cut when they are
+/// longer than limit. Code blocks that span chunks are closed at the end of a
+/// chunk and reopened at the start of the next one, which needs room for the fences.
+///
+/// A limit below 16 is treated as 16, so text longer than such a limit is split into
+/// chunks of up to 16 characters.
+///
+/// Empty text and text that is only whitespace, which slack rejects with `no_text`,
+/// has no chunks.
+pub fn split(text: &str, limit: usize) -> Vec<String> {
+    if text.trim().is_empty() {
+        return Vec::new();
+    }
+    if text.chars().count() <= limit {
+        return vec![text.to_string()];
+    }
+
+    let mut splitter = Splitter {
+        limit: cmp::max(limit, 16),
+        chunks: Vec::new(),
+        current: String::new(),
+        len: 0,
+        in_fence: false,
+    };
+    let mut lines = text.split('\n').peekable();
+    while let Some(line) = lines.next() {
+        let toggles_fence = line.matches(FENCE).count() % 2 == 1;
+        if toggles_fence && splitter.in_fence && splitter.is_fresh() {
+            // the previous chunk already closed this code block
+            splitter.current.clear();
+            splitter.len = 0;
+        } else {
+            let end = if lines.peek().is_some() { "\n" } else { "" };
+            splitter.push_line(line, end);
+        }
+        if toggles_fence {
+            splitter.in_fence = !splitter.in_fence;
+        }
+    }
+    splitter.finish()
+}
+
+const FENCE: &'static str = "```";
+
+struct Splitter {
+    limit: usize,
+    chunks: Vec<String>,
+    current: String,
+    len: usize,
+    in_fence: bool,
+}
+
+impl Splitter {
+    /// Space needed to close an open code block at the end of the chunk.
+    fn reserve(&self) -> usize {
+        if self.in_fence { FENCE.len() + 1 } else { 0 }
+    }
+
+    /// Whether the current chunk has nothing but the reopened code block in it.
+    fn is_fresh(&self) -> bool {
+        self.current.trim().is_empty() || (self.in_fence && self.current == format!("{}\n", FENCE))
+    }
+
+    fn push_line(&mut self, line: &str, end: &str) {
+        let n = line.chars().count() + end.len();
+        if self.len + n + self.reserve() > self.limit && !self.is_fresh() {
+            self.flush();
+        }
+        if self.len + n + self.reserve() <= self.limit {
+            self.current.push_str(line);
+            self.current.push_str(end);
+            self.len += n;
+            return;
+        }
+        for word in words(line) {
+            self.push(word);
+        }
+        self.push(end);
+    }
+
+    fn push(&mut self, piece: &str) {
+        let n = piece.chars().count();
+        if self.len + n + self.reserve() > self.limit && !self.is_fresh() {
+            self.flush();
+        }
+        // whitespace isn't carried over to the start of a chunk, even into a reopened code block
+        if self.is_fresh() && piece.trim().is_empty() {
+            return;
+        }
+        if self.len + n + self.reserve() <= self.limit {
+            self.current.push_str(piece);
+            self.len += n;
+            return;
+        }
+        // a single word that doesn't fit in a chunk of its own
+        for c in piece.chars() {
+            if self.len + 1 + self.reserve() > self.limit {
+                self.flush();
+            }
+            self.current.push(c);
+            self.len += 1;
+        }
+    }
+
+    fn flush(&mut self) {
+        let mut chunk = mem::replace(&mut self.current, String::new());
+        let trimmed = chunk.trim_right().len();
+        chunk.truncate(trimmed);
+        if self.in_fence {
+            chunk.push('\n');
+            chunk.push_str(FENCE);
+            self.current.push_str(FENCE);
+            self.current.push('\n');
+        }
+        if !chunk.trim().is_empty() {
+            self.chunks.push(chunk);
+        }
+        self.len = self.current.chars().count();
+    }
+
+    fn finish(mut self) -> Vec<String> {
+        self.in_fence = false;
+        self.flush();
+        self.chunks
+    }
+}
+
+/// Splits a line into words with their trailing whitespace, keeping `<...>` sequences whole.
+fn words(line: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut start = 0;
+    let mut in_control = false;
+    let mut prev_space = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '<' => in_control = true,
+            '>' => in_control = false,
+            _ => {}
+        }
+        let space = c.is_whitespace() && !in_control;
+        if prev_space && !space {
+            words.push(&line[start..i]);
+            start = i;
+        }
+        prev_space = space;
+    }
+    if start < line.len() {
+        words.push(&line[start..]);
+    }
+    words
+}
+
+/// The output formats supported by `render`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RenderMode {
+    /// Plain text, links are written as `label (url)` and markup is kept as is.
+    Plain,
+    /// Markdown, links are written as `[label](url)` and other markdown characters are escaped.
+    Markdown,
+    /// Html, text is html escaped and links become anchors.
+    Html,
+}
+
+/// Renders slack message text in a human readable form, with mentions replaced by names.
+/// user_name and channel_name are used to look up the names of ids that arrive without
+/// a label, the id itself is used when they return None.
+///
+/// For Markdown and Html, the `*bold*`, `_italic_`, `~strike~`, `` `code` `` and
+/// ```` ``` ```` block markup of the text is converted too. Mentions and links in code are
+/// rendered as plain text.
+pub fn render<U, C>(text: &str, mode: RenderMode, user_name: U, channel_name: C) -> String
+    where U: Fn(&str) -> Option<String>,
+          C: Fn(&str) -> Option<String>
+{
+    let mut out = String::with_capacity(text.len());
+    if mode == RenderMode::Plain {
+        render_tokens(&mut out, text, mode, &user_name, &channel_name);
+        return out;
+    }
+    for span in spans(text) {
+        match span {
+            Span::Text(text) => render_tokens(&mut out, text, mode, &user_name, &channel_name),
+            Span::Open(style) => out.push_str(style.tags(mode).0),
+            Span::Close(style) => out.push_str(style.tags(mode).1),
+            Span::Code(code) => {
+                let mut plain = String::new();
+                render_tokens(&mut plain, code, RenderMode::Plain, &user_name, &channel_name);
+                match mode {
+                    RenderMode::Html => out.push_str(&format!("<code>{}</code>", html_escape(&plain))),
+                    _ => out.push_str(&format!("`{}`", plain)),
+                }
+            }
+            Span::Pre(code) => {
+                let mut plain = String::new();
+                render_tokens(&mut plain, code, RenderMode::Plain, &user_name, &channel_name);
+                match mode {
+                    RenderMode::Html => out.push_str(&format!("<pre>{}</pre>", html_escape(&plain))),
+                    _ => out.push_str(&format!("{}\n{}\n{}", FENCE, plain, FENCE)),
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Renders the plain text, mentions and links of text, see `render`.
+fn render_tokens<U, C>(out: &mut String, text: &str, mode: RenderMode, user_name: &U, channel_name: &C)
+    where U: Fn(&str) -> Option<String>,
+          C: Fn(&str) -> Option<String>
+{
+    for token in parse(text) {
+        let plain = match token {
+            Token::Text(text) => text,
+            Token::User { id, label } => {
+                format!("@{}", label.or_else(|| user_name(&id)).unwrap_or(id))
+            }
+            Token::Channel { id, label } => {
+                format!("#{}", label.or_else(|| channel_name(&id)).unwrap_or(id))
+            }
+            Token::UserGroup { id, label } => label.unwrap_or_else(|| format!("@{}", id)),
+            Token::Special { name, label } => label.unwrap_or_else(|| format!("@{}", name)),
+            Token::Date { fallback, .. } => fallback,
+            Token::Link { url, label } => {
+                match mode {
+                    RenderMode::Plain => {
+                        match label {
+                            Some(label) => out.push_str(&format!("{} ({})", label, url)),
+                            None => out.push_str(&url),
+                        }
+                    }
+                    RenderMode::Markdown => {
+                        match label {
+                            Some(label) => out.push_str(&format!("[{}]({})", markdown_escape(&label), url)),
+                            None => out.push_str(&format!("<{}>", url)),
+                        }
+                    }
+                    RenderMode::Html => {
+                        out.push_str(&format!("<a href=\"{}\">{}</a>",
+                                              html_escape(&url),
+                                              html_escape(label.as_ref().unwrap_or(&url))))
+                    }
+                }
+                continue;
+            }
+        };
+        match mode {
+            RenderMode::Plain => out.push_str(&plain),
+            RenderMode::Markdown => out.push_str(&markdown_escape(&plain)),
+            RenderMode::Html => out.push_str(&html_escape(&plain).replace('\n', "<br>\n")),
+        }
+    }
+}
+
+fn html_escape(text: &str) -> String {
+    escape(text).replace('"', "&quot;")
+}
+
+/// Escapes the characters that markdown reads as markup.
+fn markdown_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | '`' | '*' | '_' | '~' | '[' | ']' | '<' | '>' => out.push('\\'),
+            _ => {}
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// The text styles of slack's mrkdwn markup.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Style {
+    Bold,
+    Italic,
+    Strike,
+}
+
+impl Style {
+    fn from_marker(marker: char) -> Option<Style> {
+        match marker {
+            '*' => Some(Style::Bold),
+            '_' => Some(Style::Italic),
+            '~' => Some(Style::Strike),
+            _ => None,
+        }
+    }
+
+    /// Returns the opening and closing tags of the style in mode.
+    fn tags(self, mode: RenderMode) -> (&'static str, &'static str) {
+        match (self, mode) {
+            (Style::Bold, RenderMode::Html) => ("<b>", "</b>"),
+            (Style::Italic, RenderMode::Html) => ("<i>", "</i>"),
+            (Style::Strike, RenderMode::Html) => ("<s>", "</s>"),
+            (Style::Bold, _) => ("**", "**"),
+            (Style::Italic, _) => ("*", "*"),
+            (Style::Strike, _) => ("~~", "~~"),
+        }
+    }
+}
+
+/// A piece of slack message text, split up by its mrkdwn markup.
+#[derive(Clone, Debug, PartialEq)]
+enum Span<'a> {
+    /// Text with no markup but mentions and links.
+    Text(&'a str),
+    Open(Style),
+    Close(Style),
+    /// The text of an inline `` `code` `` span.
+    Code(&'a str),
+    /// The text of a ```` ``` ```` block, without the line breaks around it.
+    Pre(&'a str),
+}
+
+/// Splits message text by its mrkdwn markup. Markers that aren't closed are kept as text.
+fn spans(text: &str) -> Vec<Span> {
+    let mut spans = Vec::new();
+    push_spans(text, &mut spans);
+    spans
+}
+
+fn push_spans<'a>(text: &'a str, spans: &mut Vec<Span<'a>>) {
+    let mut start = 0;
+    let mut i = 0;
+    while i < text.len() {
+        let rest = &text[i..];
+        let c = rest.chars().next().unwrap_or(' ');
+        let span = if rest.starts_with(FENCE) {
+            rest[FENCE.len()..].find(FENCE).map(|end| {
+                (Span::Pre(rest[FENCE.len()..FENCE.len() + end].trim_matches('\n')), end + 2 * FENCE.len())
+            })
+        } else if c == '`' {
+            match rest[1..].find(|c| c == '`' || c == '\n') {
+                Some(end) if end > 0 && rest[1 + end..].starts_with('`') => {
+                    Some((Span::Code(&rest[1..1 + end]), end + 2))
+                }
+                _ => None,
+            }
+        } else if c == '<' {
+            // mentions and links are kept whole, e.g. a url with underscores in it
+            if let Some(end) = rest.find('>') {
+                i += end + 1;
+                continue;
+            }
+            None
+        } else {
+            match (Style::from_marker(c), closing_marker(text, i, c)) {
+                (Some(style), Some(end)) => {
+                    if start < i {
+                        spans.push(Span::Text(&text[start..i]));
+                    }
+                    spans.push(Span::Open(style));
+                    push_spans(&text[i + 1..end], spans);
+                    spans.push(Span::Close(style));
+                    i = end + 1;
+                    start = i;
+                    continue;
+                }
+                _ => None,
+            }
+        };
+        match span {
+            Some((span, len)) => {
+                if start < i {
+                    spans.push(Span::Text(&text[start..i]));
+                }
+                spans.push(span);
+                i += len;
+                start = i;
+            }
+            None => i += c.len_utf8(),
+        }
+    }
+    if start < text.len() {
+        spans.push(Span::Text(&text[start..]));
+    }
+}
+
+/// Returns the index of the marker that closes the one at start, if any. Markers open at
+/// the start of a word and close at the end of one, on the same line.
+fn closing_marker(text: &str, start: usize, marker: char) -> Option<usize> {
+    let opens = text[..start].chars().next_back().map_or(true, |c| !c.is_alphanumeric()) &&
+                text[start + 1..].chars().next().map_or(false, |c| !c.is_whitespace() && c != marker);
+    if !opens {
+        return None;
+    }
+    let mut i = start + 1;
+    while i < text.len() {
+        let rest = &text[i..];
+        let c = rest.chars().next().unwrap_or('\n');
+        if c == '\n' {
+            return None;
+        }
+        if c == '<' {
+            if let Some(end) = rest.find('>') {
+                i += end + 1;
+                continue;
+            }
+        }
+        if c == marker && !text[..i].ends_with(char::is_whitespace) &&
+           text[i + 1..].chars().next().map_or(true, |c| !c.is_alphanumeric()) {
+            return Some(i);
+        }
+        i += c.len_utf8();
+    }
+    None
+}
+
+/// Parses the inside of a `<...>` control sequence.
+fn parse_control(inner: &str) -> Option<Token> {
+    if inner.is_empty() {
+        return None;
+    }
+    let (target, label) = match inner.find('|') {
+        Some(i) => (&inner[..i], Some(unescape(&inner[i + 1..]))),
+        None => (inner, None),
+    };
+
+    if target.starts_with('@') {
+        Some(Token::User {
+            id: target[1..].to_string(),
+            label: label,
+        })
+    } else if target.starts_with('#') {
+        Some(Token::Channel {
+            id: target[1..].to_string(),
+            label: label,
+        })
+    } else if target.starts_with("!subteam^") {
+        Some(Token::UserGroup {
+            id: target["!subteam^".len()..].to_string(),
+            label: label,
+        })
+    } else if target.starts_with("!date^") {
+        let mut parts = target["!date^".len()..].splitn(3, '^');
+        let timestamp = match parts.next().and_then(|ts| ts.parse().ok()) {
+            Some(ts) => ts,
+            None => return None,
+        };
+        let format = match parts.next() {
+            Some(format) => format.to_string(),
+            None => return None,
+        };
+        Some(Token::Date {
+            timestamp: timestamp,
+            format: format,
+            link: parts.next().map(|s| s.to_string()),
+            fallback: label.unwrap_or_default(),
+        })
+    } else if target.starts_with('!') {
+        Some(Token::Special {
+            name: target[1..].to_string(),
+            label: label,
+        })
+    } else {
+        Some(Token::Link {
+            url: unescape(target),
+            label: label,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_round_trip() {
+        let text = "if a < b && b > c";
+        assert_eq!(escape(text), "if a &lt; b &amp;&amp; b &gt; c");
+        assert_eq!(unescape(&escape(text)), text);
+        assert_eq!(unescape("&amp;lt;"), "&lt;");
+    }
+
+    #[test]
+    fn format_mentions() {
+        assert_eq!(user_mention("U123"), "<@U123>");
+        assert_eq!(channel_mention("C123"), "<#C123>");
+        assert_eq!(usergroup_mention("S123"), "<!subteam^S123>");
+        assert_eq!(link("https://example.com/?a=1&b=2", Some("a < b")),
+                   "<https://example.com/?a=1&amp;b=2|a &lt; b>");
+        assert_eq!(date(1392734382, "{date_short}", None, "Feb 18, 2014"),
+                   "<!date^1392734382^{date_short}|Feb 18, 2014>");
+    }
+
+    #[test]
+    fn parse_message_text() {
+        let tokens = parse("<@U123|bob> see <#C456|general> &amp; <!here> <https://example.com>");
+        assert_eq!(tokens,
+                   vec![Token::User { id: "U123".into(), label: Some("bob".into()) },
+                        Token::Text(" see ".into()),
+                        Token::Channel { id: "C456".into(), label: Some("general".into()) },
+                        Token::Text(" & ".into()),
+                        Token::Special { name: "here".into(), label: None },
+                        Token::Text(" ".into()),
+                        Token::Link { url: "https://example.com".into(), label: None }]);
+    }
+
+    #[test]
+    fn parse_round_trip() {
+        let text = "<!subteam^S1|@ops> at <!date^1392734382^{time}^https://x.y|6:39 AM> a &lt; b";
+        let formatted: String = parse(text).iter().map(|t| t.to_string()).collect();
+        assert_eq!(formatted, text);
+    }
+
+    #[test]
+    fn render_modes() {
+        let text = "<@U123> see <#C456> &amp; <https://example.com|docs>";
+        let users = |id: &str| if id == "U123" { Some("bob".to_string()) } else { None };
+        let channels = |_: &str| None;
+        assert_eq!(render(text, RenderMode::Plain, &users, &channels),
+                   "@bob see #C456 & docs (https://example.com)");
+        assert_eq!(render(text, RenderMode::Markdown, &users, &channels),
+                   "@bob see #C456 & [docs](https://example.com)");
+        assert_eq!(render(text, RenderMode::Html, &users, &channels),
+                   "@bob see #C456 &amp; <a href=\"https://example.com\">docs</a>");
+    }
+
+    #[test]
+    fn render_markup() {
+        let text = "*bold* _it_ ~gone~ `a*b*c` snake_case_name <https://x.y/a_b_c|a_b>";
+        let users = |id: &str| if id == "U123" { Some("bob".to_string()) } else { None };
+        let channels = |_: &str| None;
+        assert_eq!(render(text, RenderMode::Plain, &users, &channels),
+                   "*bold* _it_ ~gone~ `a*b*c` snake_case_name a_b (https://x.y/a_b_c)");
+        assert_eq!(render(text, RenderMode::Markdown, &users, &channels),
+                   "**bold** *it* ~~gone~~ `a*b*c` snake\\_case\\_name [a\\_b](https://x.y/a_b_c)");
+        assert_eq!(render(text, RenderMode::Html, &users, &channels),
+                   "<b>bold</b> <i>it</i> <s>gone</s> <code>a*b*c</code> snake_case_name \
+                    <a href=\"https://x.y/a_b_c\">a_b</a>");
+
+        let text = "*hi <@U123>* _*both*_\n```\nif a &lt; b {}\n```";
+        assert_eq!(render(text, RenderMode::Markdown, &users, &channels),
+                   "**hi @bob** ***both***\n```\nif a < b {}\n```");
+        assert_eq!(render(text, RenderMode::Html, &users, &channels),
+                   "<b>hi @bob</b> <i><b>both</b></i><br>\n<pre>if a &lt; b {}</pre>");
+    }
+
+    #[test]
+    fn render_unmatched_markup() {
+        let users = |_: &str| None;
+        let channels = |_: &str| None;
+        assert_eq!(render("2 * 3 * 4 and *a\nb* `x", RenderMode::Markdown, &users, &channels),
+                   "2 \\* 3 \\* 4 and \\*a\nb\\* \\`x");
+        assert_eq!(render("2 * 3 * 4 and *a\nb* `x", RenderMode::Html, &users, &channels),
+                   "2 * 3 * 4 and *a<br>\nb* `x");
+    }
+
+    #[test]
+    fn split_short_text() {
+        assert_eq!(split("hello world", 20), vec!["hello world"]);
+    }
+
+    #[test]
+    fn split_on_lines_then_words() {
+        let text = "first line here\nsecond line is a bit longer than the rest";
+        assert_eq!(split(text, 30),
+                   vec!["first line here", "second line is a bit longer", "than the rest"]);
+        assert_eq!(split("aaaaaaaaaaaaaaaaaaaaaaaaa", 16), vec!["aaaaaaaaaaaaaaaa", "aaaaaaaaa"]);
+    }
+
+    #[test]
+    fn split_whitespace_only() {
+        assert!(split("", 20).is_empty());
+        assert!(split(" \n ", 20).is_empty());
+        assert!(split(&" ".repeat(50), 20).is_empty());
+        assert!(split(&"\n".repeat(50), 20).is_empty());
+    }
+
+    #[test]
+    fn split_raises_small_limits() {
+        assert_eq!(split("short", 2), vec!["short"]);
+        assert_eq!(split(&"a".repeat(20), 4), vec!["a".repeat(16), "a".repeat(4)]);
+    }
+
+    #[test]
+    fn split_keeps_mentions_whole() {
+        let text = "ping <#C123|my channel> and <@U123|some user> now";
+        for chunk in split(text, 24) {
+            assert_eq!(chunk.matches('<').count(), chunk.matches('>').count());
+        }
+    }
+
+    #[test]
+    fn split_reopens_code_fences() {
+        let text = "```\nline one\nline two\nline three\n```";
+        let chunks = split(text, 26);
+        assert_eq!(chunks, vec!["```\nline one\nline two\n```", "```\nline three\n```"]);
+        for chunk in chunks {
+            assert!(chunk.chars().count() <= 26);
+        }
+    }
+
+    #[test]
+    fn split_code_fence_at_word_boundary() {
+        let text = format!("```\n{}\n```", "x ".repeat(30));
+        let chunks = split(&text, 20);
+        assert_eq!(chunks.len(), 5);
+        for chunk in chunks {
+            assert_eq!(chunk, "```\nx x x x x x\n```");
+        }
+    }
+
+    #[test]
+    fn parse_unterminated() {
+        assert_eq!(parse("a <b"), vec![Token::Text("a <b".into())]);
+        assert_eq!(parse("<>"), vec![Token::Text("<>".into())]);
+    }
+}
@@ -0,0 +1,521 @@
+//
+// Copyright 2014-2016 the slack-rs authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Handling button clicks, modals, shortcuts and message actions, see
+//! https://api.slack.com/interactivity/handling
+//!
+//! Interactions arrive either as a form encoded HTTP request, see `Interaction::from_request`,
+//! or as the payload of an `interactive` Socket Mode envelope, see
+//! `Interaction::from_envelope`. A `Dispatcher` passes them to the handler registered for
+//! their `action_id` or `callback_id`.
+
+use std::collections::{BTreeMap, HashMap};
+
+use hyper;
+use rustc_serialize::json::Json;
+use url::form_urlencoded;
+
+use error::Error;
+use signature;
+use socket_mode::Envelope;
+use web;
+use RtmClient;
+
+/// An interactive element that was used, such as a button or a select menu.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Action {
+    pub action_id: String,
+    pub block_id: Option<String>,
+    /// The type of the element, e.g. `button` or `static_select`.
+    pub kind: String,
+    /// The value of a button, or of the selected option of a select menu.
+    pub value: Option<String>,
+    pub action_ts: Option<String>,
+}
+
+impl Action {
+    fn from_json(json: &Json) -> Result<Action, Error> {
+        Ok(Action {
+            action_id: try!(web::required(json, &["action_id"], "interaction")),
+            block_id: web::string(json, &["block_id"]),
+            kind: try!(web::required(json, &["type"], "interaction")),
+            value: web::string(json, &["value"]).or_else(|| web::string(json, &["selected_option", "value"])),
+            action_ts: web::string(json, &["action_ts"]),
+        })
+    }
+}
+
+/// A modal or App Home view, see https://api.slack.com/reference/surfaces/views
+#[derive(Clone, Debug, PartialEq)]
+pub struct View {
+    pub id: String,
+    pub callback_id: Option<String>,
+    pub external_id: Option<String>,
+    pub private_metadata: Option<String>,
+    /// Pass this to `RtmClient::views_update` to avoid overwriting a newer version.
+    pub hash: Option<String>,
+    /// The whole view as sent by slack.
+    pub raw: Json,
+}
+
+impl View {
+    /// Reads a view from json.
+    pub fn from_json(json: &Json) -> Result<View, Error> {
+        Ok(View {
+            id: try!(web::required(json, &["id"], "interaction")),
+            callback_id: web::string(json, &["callback_id"]).and_then(|c| if c.is_empty() { None } else { Some(c) }),
+            external_id: web::string(json, &["external_id"]).and_then(|e| if e.is_empty() { None } else { Some(e) }),
+            private_metadata: web::string(json, &["private_metadata"]),
+            hash: web::string(json, &["hash"]),
+            raw: json.clone(),
+        })
+    }
+
+    /// Returns the value the user entered in an input element of a submitted view: the text
+    /// of a text input, or the value of the selected option of a select menu.
+    pub fn value(&self, block_id: &str, action_id: &str) -> Option<String> {
+        self.raw.find_path(&["state", "values", block_id, action_id]).and_then(|input| {
+            web::string(input, &["value"]).or_else(|| web::string(input, &["selected_option", "value"]))
+        })
+    }
+}
+
+/// An interaction payload, see https://api.slack.com/reference/interaction-payloads
+#[derive(Clone, Debug, PartialEq)]
+pub enum Interaction {
+    /// An element in a message or a view was used.
+    BlockActions {
+        user_id: String,
+        team_id: Option<String>,
+        trigger_id: Option<String>,
+        /// Set for actions in messages.
+        response_url: Option<String>,
+        /// Set for actions in messages.
+        channel_id: Option<String>,
+        /// Set for actions in messages.
+        message_ts: Option<String>,
+        /// Set for actions in views.
+        view: Option<View>,
+        actions: Vec<Action>,
+    },
+    /// A modal was submitted.
+    ViewSubmission {
+        user_id: String,
+        team_id: Option<String>,
+        trigger_id: Option<String>,
+        view: View,
+    },
+    /// A modal was closed, only sent if the view had `notify_on_close` set.
+    ViewClosed {
+        user_id: String,
+        team_id: Option<String>,
+        view: View,
+        is_cleared: bool,
+    },
+    /// A global shortcut was used.
+    Shortcut {
+        user_id: String,
+        team_id: Option<String>,
+        callback_id: String,
+        trigger_id: String,
+    },
+    /// A message shortcut was used on a message.
+    MessageAction {
+        user_id: String,
+        team_id: Option<String>,
+        callback_id: String,
+        trigger_id: String,
+        channel_id: String,
+        message_ts: String,
+        response_url: Option<String>,
+    },
+}
+
+impl Interaction {
+    /// Reads an interaction from its json payload.
+    pub fn from_json(json: &Json) -> Result<Interaction, Error> {
+        let kind = try!(web::required(json, &["type"], "interaction"));
+        let user_id = try!(web::required(json, &["user", "id"], "interaction"));
+        let team_id = web::string(json, &["team", "id"]);
+        let view = match json.find("view") {
+            Some(view) if !view.is_null() => Some(try!(View::from_json(view))),
+            _ => None,
+        };
+        let missing_view = || Error::Internal(format!("{} interaction without view", kind));
+        match &kind[..] {
+            "block_actions" => {
+                let mut actions = Vec::new();
+                if let Some(array) = json.find("actions").and_then(|a| a.as_array()) {
+                    for action in array.iter() {
+                        actions.push(try!(Action::from_json(action)));
+                    }
+                }
+                Ok(Interaction::BlockActions {
+                    user_id: user_id,
+                    team_id: team_id,
+                    trigger_id: web::string(json, &["trigger_id"]),
+                    response_url: web::string(json, &["response_url"]),
+                    channel_id: web::string(json, &["channel", "id"]),
+                    message_ts: web::string(json, &["message", "ts"]),
+                    view: view,
+                    actions: actions,
+                })
+            }
+            "view_submission" => {
+                Ok(Interaction::ViewSubmission {
+                    user_id: user_id,
+                    team_id: team_id,
+                    trigger_id: web::string(json, &["trigger_id"]),
+                    view: try!(view.ok_or_else(missing_view)),
+                })
+            }
+            "view_closed" => {
+                Ok(Interaction::ViewClosed {
+                    user_id: user_id,
+                    team_id: team_id,
+                    view: try!(view.ok_or_else(missing_view)),
+                    is_cleared: json.find("is_cleared").and_then(|c| c.as_boolean()).unwrap_or(false),
+                })
+            }
+            "shortcut" => {
+                Ok(Interaction::Shortcut {
+                    user_id: user_id,
+                    team_id: team_id,
+                    callback_id: try!(web::required(json, &["callback_id"], "interaction")),
+                    trigger_id: try!(web::required(json, &["trigger_id"], "interaction")),
+                })
+            }
+            "message_action" => {
+                Ok(Interaction::MessageAction {
+                    user_id: user_id,
+                    team_id: team_id,
+                    callback_id: try!(web::required(json, &["callback_id"], "interaction")),
+                    trigger_id: try!(web::required(json, &["trigger_id"], "interaction")),
+                    channel_id: try!(web::required(json, &["channel", "id"], "interaction")),
+                    message_ts: try!(web::required(json, &["message", "ts"], "interaction")),
+                    response_url: web::string(json, &["response_url"]),
+                })
+            }
+            _ => Err(Error::Internal(format!("unknown interaction type: {}", kind))),
+        }
+    }
+
+    /// Parses the `application/x-www-form-urlencoded` body of an interaction request,
+    /// which holds the json payload in its `payload` field.
+    /// The body is not verified, see `from_request`.
+    pub fn from_form(body: &[u8]) -> Result<Interaction, Error> {
+        match form_urlencoded::parse(body).find(|&(ref k, _)| k == "payload") {
+            Some((_, payload)) => Interaction::from_json(&try!(Json::from_str(&payload))),
+            None => Err(Error::Internal(String::from("interaction request without payload"))),
+        }
+    }
+
+    /// Verifies an interaction request with signing_secret against the values of the
+    /// `X-Slack-Request-Timestamp` and `X-Slack-Signature` headers, then parses its body.
+    pub fn from_request(signing_secret: &str, timestamp: &str, signature: &str, body: &[u8]) -> Result<Interaction, Error> {
+        try!(signature::verify(signing_secret, timestamp, signature, body));
+        Interaction::from_form(body)
+    }
+
+    /// Reads an interaction from an `interactive` Socket Mode envelope.
+    pub fn from_envelope(envelope: &Envelope) -> Result<Interaction, Error> {
+        match (&envelope.kind[..], envelope.payload.as_ref()) {
+            ("interactive", Some(payload)) => Interaction::from_json(payload),
+            _ => Err(Error::Internal(format!("not an interactive envelope: {}", envelope.kind))),
+        }
+    }
+
+    /// Returns the id of the user that interacted.
+    pub fn user_id(&self) -> &str {
+        match *self {
+            Interaction::BlockActions { ref user_id, .. } |
+            Interaction::ViewSubmission { ref user_id, .. } |
+            Interaction::ViewClosed { ref user_id, .. } |
+            Interaction::Shortcut { ref user_id, .. } |
+            Interaction::MessageAction { ref user_id, .. } => user_id,
+        }
+    }
+}
+
+/// The response to a `view_submission`, sent as the body of the HTTP response or as the
+/// payload of the Socket Mode acknowledgement.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ResponseAction {
+    /// Keeps the modal open and shows error messages, keyed by the block_id of the input.
+    Errors(Vec<(String, String)>),
+    /// Replaces the modal with a view, given as json.
+    Update(String),
+    /// Pushes a view, given as json, onto the modal.
+    Push(String),
+    /// Closes all views of the modal.
+    Clear,
+}
+
+impl ResponseAction {
+    /// Returns the response as json, failing if a view isn't valid json.
+    pub fn to_json(&self) -> Result<Json, Error> {
+        let mut json = BTreeMap::new();
+        let action = match *self {
+            ResponseAction::Errors(ref errors) => {
+                let errors = errors.iter().map(|&(ref k, ref v)| (k.clone(), Json::String(v.clone()))).collect();
+                json.insert(String::from("errors"), Json::Object(errors));
+                "errors"
+            }
+            ResponseAction::Update(ref view) => {
+                json.insert(String::from("view"), try!(Json::from_str(view)));
+                "update"
+            }
+            ResponseAction::Push(ref view) => {
+                json.insert(String::from("view"), try!(Json::from_str(view)));
+                "push"
+            }
+            ResponseAction::Clear => "clear",
+        };
+        json.insert(String::from("response_action"), Json::String(action.to_string()));
+        Ok(Json::Object(json))
+    }
+}
+
+/// Handles an action, with the interaction it is part of.
+pub type ActionHandler<'a> = Box<FnMut(&mut RtmClient, &Interaction, &Action) + 'a>;
+/// Handles a view submission or close, returning the response for submissions.
+pub type ViewHandler<'a> = Box<FnMut(&mut RtmClient, &Interaction, &View) -> Option<ResponseAction> + 'a>;
+/// Handles a shortcut or message action.
+pub type ShortcutHandler<'a> = Box<FnMut(&mut RtmClient, &Interaction) + 'a>;
+
+/// Passes interactions to the handlers registered for their `action_id` or `callback_id`.
+///
+/// ```no_run
+/// use slack::interactive::{Dispatcher, ResponseAction};
+///
+/// let dispatcher = Dispatcher::new()
+///     .on_action("approve", |cli, interaction, _| {
+///         let _ = cli.post_message("#approvals", &format!("approved by <@{}>", interaction.user_id()), None);
+///     })
+///     .on_view("request_form", |_, _, view| {
+///         match view.value("reason", "reason_input") {
+///             Some(_) => None,
+///             None => Some(ResponseAction::Errors(vec![("reason".into(), "Give a reason".into())])),
+///         }
+///     });
+/// ```
+pub struct Dispatcher<'a> {
+    actions: HashMap<String, ActionHandler<'a>>,
+    views: HashMap<String, ViewHandler<'a>>,
+    shortcuts: HashMap<String, ShortcutHandler<'a>>,
+}
+
+impl<'a> Dispatcher<'a> {
+    /// Creates a dispatcher without handlers.
+    pub fn new() -> Dispatcher<'a> {
+        Dispatcher {
+            actions: HashMap::new(),
+            views: HashMap::new(),
+            shortcuts: HashMap::new(),
+        }
+    }
+
+    /// Registers the handler of the block actions with action_id.
+    pub fn on_action<F>(mut self, action_id: &str, handler: F) -> Dispatcher<'a>
+        where F: FnMut(&mut RtmClient, &Interaction, &Action) + 'a
+    {
+        self.actions.insert(action_id.to_string(), Box::new(handler));
+        self
+    }
+
+    /// Registers the handler of the submission and closing of views with callback_id.
+    pub fn on_view<F>(mut self, callback_id: &str, handler: F) -> Dispatcher<'a>
+        where F: FnMut(&mut RtmClient, &Interaction, &View) -> Option<ResponseAction> + 'a
+    {
+        self.views.insert(callback_id.to_string(), Box::new(handler));
+        self
+    }
+
+    /// Registers the handler of the shortcuts and message actions with callback_id.
+    pub fn on_shortcut<F>(mut self, callback_id: &str, handler: F) -> Dispatcher<'a>
+        where F: FnMut(&mut RtmClient, &Interaction) + 'a
+    {
+        self.shortcuts.insert(callback_id.to_string(), Box::new(handler));
+        self
+    }
+
+    /// Passes interaction to the matching handlers. Returns the response action of a view
+    /// submission handler, if any. Interactions without a handler are ignored.
+    pub fn dispatch(&mut self, cli: &mut RtmClient, interaction: &Interaction) -> Option<ResponseAction> {
+        match *interaction {
+            Interaction::BlockActions { ref actions, .. } => {
+                for action in actions.iter() {
+                    if let Some(handler) = self.actions.get_mut(&action.action_id) {
+                        handler(cli, interaction, action);
+                    }
+                }
+                None
+            }
+            Interaction::ViewSubmission { ref view, .. } |
+            Interaction::ViewClosed { ref view, .. } => {
+                let handler = view.callback_id.as_ref().and_then(|id| self.views.get_mut(id));
+                match handler {
+                    Some(handler) => handler(cli, interaction, view),
+                    None => None,
+                }
+            }
+            Interaction::Shortcut { ref callback_id, .. } |
+            Interaction::MessageAction { ref callback_id, .. } => {
+                if let Some(handler) = self.shortcuts.get_mut(callback_id) {
+                    handler(cli, interaction);
+                }
+                None
+            }
+        }
+    }
+}
+
+impl<'a> Default for Dispatcher<'a> {
+    fn default() -> Dispatcher<'a> {
+        Dispatcher::new()
+    }
+}
+
+impl RtmClient {
+    /// Calls a views method and reads the view of the response.
+    fn call_views(&self, method: &str, params: &[(&str, &str)]) -> Result<View, Error> {
+        let client = hyper::Client::new();
+        let json = try!(web::call_json(&client, method, params));
+        match json.find("view") {
+            Some(view) => View::from_json(view),
+            None => Err(Error::Internal(format!("{} response without view", method))),
+        }
+    }
+
+    /// Wraps https://api.slack.com/methods/views.open to open a modal with a view, given as
+    /// json, using the trigger_id of an interaction or slash command.
+    pub fn views_open(&self, trigger_id: &str, view: &str) -> Result<View, Error> {
+        self.call_views("views.open",
+                        &[("token", &self.token[..]), ("trigger_id", trigger_id), ("view", view)])
+    }
+
+    /// Wraps https://api.slack.com/methods/views.push to push a view, given as json, onto the
+    /// open modal of an interaction.
+    pub fn views_push(&self, trigger_id: &str, view: &str) -> Result<View, Error> {
+        self.call_views("views.push",
+                        &[("token", &self.token[..]), ("trigger_id", trigger_id), ("view", view)])
+    }
+
+    /// Wraps https://api.slack.com/methods/views.update to replace the view with view_id by a
+    /// view, given as json. If hash is set, the update fails if the view changed since.
+    pub fn views_update(&self, view_id: &str, view: &str, hash: Option<&str>) -> Result<View, Error> {
+        let mut params = vec![("token", &self.token[..]), ("view_id", view_id), ("view", view)];
+        web::push_opt(&mut params, "hash", hash);
+        self.call_views("views.update", &params)
+    }
+
+    /// Wraps https://api.slack.com/methods/views.publish to publish the App Home of a user,
+    /// given as json. If hash is set, publishing fails if the view changed since.
+    pub fn views_publish(&self, user_id: &str, view: &str, hash: Option<&str>) -> Result<View, Error> {
+        let mut params = vec![("token", &self.token[..]), ("user_id", user_id), ("view", view)];
+        web::push_opt(&mut params, "hash", hash);
+        self.call_views("views.publish", &params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustc_serialize::json::Json;
+
+    fn interaction(payload: &str) -> Interaction {
+        Interaction::from_json(&Json::from_str(payload).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn parse_block_actions() {
+        let interaction = interaction(r#"{
+            "type": "block_actions",
+            "user": {"id": "U0CA5", "username": "amy"},
+            "team": {"id": "T0CAG", "domain": "acme"},
+            "trigger_id": "12321423423.333649436676.d8c1bb837935619ccad0f624c448ffb3",
+            "response_url": "https://hooks.slack.com/actions/T0CAG/1/2",
+            "channel": {"id": "C0CA1", "name": "approvals"},
+            "message": {"type": "message", "ts": "1548261231.000200"},
+            "actions": [{
+                "action_id": "approve",
+                "block_id": "request",
+                "type": "button",
+                "value": "42",
+                "action_ts": "1548426417.840180"
+            }]
+        }"#);
+        assert_eq!(interaction.user_id(), "U0CA5");
+        match interaction {
+            Interaction::BlockActions { ref channel_id, ref message_ts, ref actions, .. } => {
+                assert_eq!(channel_id.as_ref().map(|c| &c[..]), Some("C0CA1"));
+                assert_eq!(message_ts.as_ref().map(|t| &t[..]), Some("1548261231.000200"));
+                assert_eq!(actions.len(), 1);
+                assert_eq!(actions[0].action_id, "approve");
+                assert_eq!(actions[0].value, Some(String::from("42")));
+            }
+            _ => panic!("Interaction parsed into incorrect variant."),
+        }
+    }
+
+    #[test]
+    fn parse_view_submission_values() {
+        let interaction = interaction(r#"{
+            "type": "view_submission",
+            "user": {"id": "U0CA5"},
+            "view": {
+                "id": "VNHU13V36",
+                "callback_id": "request_form",
+                "hash": "156663117.cd33ad1f",
+                "state": {"values": {
+                    "reason": {"reason_input": {"type": "plain_text_input", "value": "new laptop"}},
+                    "urgency": {"urgency_select": {"type": "static_select", "selected_option": {"value": "high"}}}
+                }}
+            }
+        }"#);
+        match interaction {
+            Interaction::ViewSubmission { ref view, .. } => {
+                assert_eq!(view.callback_id, Some(String::from("request_form")));
+                assert_eq!(view.value("reason", "reason_input"), Some(String::from("new laptop")));
+                assert_eq!(view.value("urgency", "urgency_select"), Some(String::from("high")));
+                assert_eq!(view.value("reason", "missing"), None);
+            }
+            _ => panic!("Interaction parsed into incorrect variant."),
+        }
+    }
+
+    #[test]
+    fn parse_form_payload() {
+        let body = "payload=%7B%22type%22%3A%22shortcut%22%2C%22user%22%3A%7B%22id%22%3A%22U1%22%7D%2C\
+                    %22callback_id%22%3A%22new_request%22%2C%22trigger_id%22%3A%22t1%22%7D";
+        assert_eq!(Interaction::from_form(body.as_bytes()).unwrap(),
+                   Interaction::Shortcut {
+                       user_id: String::from("U1"),
+                       team_id: None,
+                       callback_id: String::from("new_request"),
+                       trigger_id: String::from("t1"),
+                   });
+    }
+
+    #[test]
+    fn errors_response_action() {
+        let action = ResponseAction::Errors(vec![(String::from("reason"), String::from("Give a reason"))]);
+        assert_eq!(action.to_json().unwrap().to_string(),
+                   r#"{"errors":{"reason":"Give a reason"},"response_action":"errors"}"#);
+        assert_eq!(ResponseAction::Clear.to_json().unwrap().to_string(),
+                   r#"{"response_action":"clear"}"#);
+    }
+}
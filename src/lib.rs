@@ -21,22 +21,46 @@
 extern crate hyper;
 extern crate websocket;
 extern crate rustc_serialize;
+extern crate url;
+extern crate crypto;
 pub extern crate slack_api as api;
 
 pub mod error;
-pub use error::Error;
+pub use error::{ApiErrorCode, Error};
 
 pub use api::{Attachment, Channel, Group, Im, Team, User, Message};
 
 mod events;
-pub use events::Event;
+pub use events::{Event, Item};
+
+pub mod chat;
+pub mod conversations;
+pub mod events_api;
+pub mod format;
+pub mod interactive;
+pub mod manager;
+pub mod files;
+pub mod mpim;
+pub mod oauth;
+pub mod pagination;
+pub mod pins;
+pub mod reactions;
+pub mod signature;
+pub mod slash;
+pub mod socket_mode;
+pub mod stars;
+pub mod users;
+pub mod webhook;
+
+mod web;
 
 use std::collections::HashMap;
 use std::io;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicIsize, Ordering};
-use std::sync::mpsc::{self, channel};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::sync::atomic::{AtomicBool, AtomicIsize, Ordering};
+use std::sync::mpsc::{self, channel, RecvTimeoutError};
 use std::thread;
+use std::time::{Duration, Instant};
 
 use rustc_serialize::json;
 
@@ -65,8 +89,10 @@ pub trait EventHandler {
     /// but you may use this event to track the connection as a keep-alive.
     fn on_ping(&mut self, cli: &mut RtmClient);
 
-    /// Called when the connection is closed for any reason.
-    fn on_close(&mut self, cli: &mut RtmClient);
+    /// Called when slack closes the connection, with the close code and reason it sent.
+    /// The code is None if the close frame had no payload; 1000 is a normal close, and
+    /// e.g. 1001 means the server is going away, as when it restarts.
+    fn on_close(&mut self, cli: &mut RtmClient, code: Option<u16>, reason: &str);
 
     /// Called when the connection is opened.
     fn on_connect(&mut self, cli: &mut RtmClient);
@@ -75,28 +101,82 @@ pub trait EventHandler {
 /// Used for passing websocket messages in channels
 pub enum WsMessage {
     Close,
+    /// Sends a close frame once the messages queued before it are sent, then waits up to
+    /// the duration for slack to close the connection.
+    CloseGracefully(Duration),
     Text(String),
     Pong(String),
 }
 
+/// The kinds of conversation, each with their own family of Web API methods.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChannelKind {
+    /// A public channel, using the `channels.*` methods.
+    Channel,
+    /// A private channel, using the `groups.*` methods.
+    Group,
+    /// A direct message channel, using the `im.*` methods.
+    Im,
+    /// A multi-party direct message channel, using the `mpim.*` methods.
+    /// Mpim ids start with a `G`, so `from_id` returns `Group` for them.
+    Mpim,
+}
+
+impl ChannelKind {
+    /// Tells the kind of conversation from the first letter of its id.
+    pub fn from_id(id: &str) -> Option<ChannelKind> {
+        match id.chars().next() {
+            Some('C') => Some(ChannelKind::Channel),
+            Some('G') => Some(ChannelKind::Group),
+            Some('D') => Some(ChannelKind::Im),
+            _ => None,
+        }
+    }
+
+    /// The prefix of the Web API methods for this kind of conversation.
+    pub fn api_prefix(&self) -> &'static str {
+        match *self {
+            ChannelKind::Channel => "channels",
+            ChannelKind::Group => "groups",
+            ChannelKind::Im => "im",
+            ChannelKind::Mpim => "mpim",
+        }
+    }
+}
+
 /// The actual messaging client.
+///
+/// Clones of a client share its caches and connection, so a clone can be moved to another
+/// thread to send messages and call Web API methods while `run` is running.
+#[derive(Clone)]
 pub struct RtmClient {
     token: String,
+    state: Arc<RwLock<State>>,
+    msg_num: Arc<AtomicIsize>,
+    outs: Arc<Mutex<Option<mpsc::Sender<WsMessage>>>>,
+    /// Set by `shutdown` and `shutdown_gracefully`, so that the close they start isn't
+    /// reported as an error.
+    closing: Arc<AtomicBool>,
+}
+
+/// The data received on login, kept up to date while running.
+#[derive(Default)]
+struct State {
     start_info: Option<api::rtm::StartResponse>,
     channels: Vec<Channel>,
     groups: Vec<Group>,
+    mpims: Vec<mpim::Mpim>,
     users: Vec<User>,
     channel_ids: HashMap<String, String>,
     group_ids: HashMap<String, String>,
     user_ids: HashMap<String, String>,
-    msg_num: Arc<AtomicIsize>,
-    outs: Option<mpsc::Sender<WsMessage>>,
 }
 
 /// Thread-safe API for sending messages asynchronously
 pub struct Sender {
     inner: mpsc::Sender<WsMessage>,
-    msg_num: Arc<AtomicIsize>
+    msg_num: Arc<AtomicIsize>,
+    closing: Arc<AtomicBool>,
 }
 
 impl Sender {
@@ -116,7 +196,7 @@ impl Sender {
     /// successfully since that runs on a separate task.
     pub fn send(&self, raw: &str) -> Result<(), Error> {
         try!(self.inner.send(WsMessage::Text(raw.to_string()))
-                       .map_err(|err| Error::Internal(format!("{}", err))));
+                       .map_err(|_| Error::NotConnected));
         Ok(())
     }
 
@@ -135,6 +215,23 @@ impl Sender {
         try!(self.send(&mstr[..]));
         Ok(n)
     }
+
+    /// Closes the connection, which makes `RtmClient::run` return Ok(())
+    pub fn shutdown(&self) -> Result<(), Error> {
+        self.closing.store(true, Ordering::SeqCst);
+        self.inner.send(WsMessage::Close)
+            .map_err(|_| Error::NotConnected)
+    }
+
+    /// Closes the connection once the messages already sent through this or any other
+    /// `Sender` are passed to slack, sending a close frame and waiting up to timeout for
+    /// slack to close the connection. `RtmClient::run` then returns Ok(()), or
+    /// `Error::Timeout` if slack didn't close the connection in time.
+    pub fn shutdown_gracefully(&self, timeout: Duration) -> Result<(), Error> {
+        self.closing.store(true, Ordering::SeqCst);
+        self.inner.send(WsMessage::CloseGracefully(timeout))
+            .map_err(|_| Error::NotConnected)
+    }
 }
 
 impl RtmClient {
@@ -143,22 +240,49 @@ impl RtmClient {
     pub fn new(token: &str) -> RtmClient {
         RtmClient {
             token: String::from(token),
-            start_info: None,
-            channels: Vec::new(),
-            groups: Vec::new(),
-            users: Vec::new(),
-            channel_ids: HashMap::new(),
-            group_ids: HashMap::new(),
-            user_ids: HashMap::new(),
+            state: Arc::new(RwLock::new(State::default())),
             msg_num: Arc::new(AtomicIsize::new(0)),
-            outs: None,
+            outs: Arc::new(Mutex::new(None)),
+            closing: Arc::new(AtomicBool::new(false)),
         }
     }
 
+    /// Locks the caches for reading. A panic while they were locked can't leave them
+    /// half updated, so a poisoned lock is used as is.
+    fn state(&self) -> RwLockReadGuard<State> {
+        self.state.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Locks the caches for writing, see `state`.
+    fn state_mut(&self) -> RwLockWriteGuard<State> {
+        self.state.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Locks the channel to the sender thread, see `state`.
+    fn outs(&self) -> MutexGuard<Option<mpsc::Sender<WsMessage>>> {
+        self.outs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns the channel to the sender thread, or `Error::NotConnected` before login.
+    fn sender(&self) -> Result<mpsc::Sender<WsMessage>, Error> {
+        self.outs().clone().ok_or(Error::NotConnected)
+    }
+
+    /// Sets the channel to the sender thread of a new connection, which isn't closing yet.
+    fn set_outs(&self, tx: mpsc::Sender<WsMessage>) {
+        *self.outs() = Some(tx);
+        self.closing.store(false, Ordering::SeqCst);
+    }
+
+    /// Whether `shutdown` or `shutdown_gracefully` was called for the connection.
+    fn closed_locally(&self) -> bool {
+        self.closing.load(Ordering::SeqCst)
+    }
+
     /// Returns the name of the bot/user connected to the client.
     /// Only valid after login, otherwise None.
     pub fn get_name(&self) -> Option<String> {
-        match self.start_info {
+        match self.state().start_info {
             Some(ref s) => Some(s.self_data.name.clone()),
             None => None,
         }
@@ -167,7 +291,7 @@ impl RtmClient {
     /// Returns the id of the bot/user connected to the client.
     /// Only valid after login, otherwise None.
     pub fn get_id(&self) -> Option<String> {
-        match self.start_info {
+        match self.state().start_info {
             Some(ref s) => Some(s.self_data.id.clone()),
             None => None,
         }
@@ -176,7 +300,7 @@ impl RtmClient {
     /// Returns the Team struct of the bot/user connected to the client.
     /// / Only valid after login, otherwise None.
     pub fn get_team(&self) -> Option<Team> {
-        match self.start_info {
+        match self.state().start_info {
             Some(ref s) => Some(s.team.clone()),
             None => None,
         }
@@ -184,67 +308,96 @@ impl RtmClient {
 
     /// Get a user id from a username
     /// Only valid after login.
-    pub fn get_user_id(&self, username: &str) -> Option<&String> {
-        self.user_ids.get(username)
+    pub fn get_user_id(&self, username: &str) -> Option<String> {
+        self.state().user_ids.get(username).cloned()
     }
 
     /// Evaluate if chan is a channel name or channel id
-    /// If channel name, returns its id
+    /// If channel or group name, returns its id
     /// If channel id, returns itself
     /// Only valid after login.
     fn evaluate_channel_id(&self, chan: &str) -> Result<String, Error> {
         let id = match chan.starts_with("#") {
             true => {
-                match self.get_channel_id(&chan[1..]) {
+                let name = &chan[1..];
+                match self.get_channel_id(name).or_else(|| self.get_group_id(name)) {
                     Some(s) => s,
-                    None => return Err(Error::Internal(String::from("need to login first to retrieve channel list"))),
+                    None => return Err(Error::api("channel_not_found", None)),
                 }
             }
-            false => chan,
+            false => chan.to_string(),
         };
 
-        Ok(id.to_string())
+        Ok(id)
+    }
+
+    /// Returns the kind of conversation chan refers to, chan can be an id or a `#name`.
+    /// Mpims are recognised by looking them up in the mpims of the client.
+    /// Only valid after login.
+    pub fn channel_kind(&self, chan: &str) -> Result<ChannelKind, Error> {
+        let id = try!(self.evaluate_channel_id(chan));
+        if self.is_mpim(&id) {
+            return Ok(ChannelKind::Mpim);
+        }
+        ChannelKind::from_id(&id).ok_or_else(|| Error::Internal(format!("unknown channel id type: {}", id)))
     }
 
     /// Get a channel id from a channel name, note that channel_name does not begin with a '#'
     /// Only valid after login.
-    pub fn get_channel_id(&self, channel_name: &str) -> Option<&String> {
-        self.channel_ids.get(channel_name)
+    pub fn get_channel_id(&self, channel_name: &str) -> Option<String> {
+        self.state().channel_ids.get(channel_name).cloned()
     }
 
     /// Get a group id from a group name
     /// Only valid after login.
-    pub fn get_group_id(&self, group_name: &str) -> Option<&String> {
-        self.group_ids.get(group_name)
+    pub fn get_group_id(&self, group_name: &str) -> Option<String> {
+        self.state().group_ids.get(group_name).cloned()
     }
 
     /// Returns a vector of Users from the team the bot/client is connected to.
     /// Only valid after login.
     pub fn get_users(&self) -> Vec<User> {
-        self.users.clone()
+        self.state().users.clone()
     }
 
     /// Returns a vector of Channels from the team the bot/client is connected to.
     /// Only valid after login.
     pub fn get_channels(&self) -> Vec<Channel> {
-        self.channels.clone()
+        self.state().channels.clone()
     }
 
     /// Returns a vector of Groups from the team the bot/client is connected to.
     /// Only valid after login.
     pub fn get_groups(&self) -> Vec<Group> {
-        self.groups.clone()
+        self.state().groups.clone()
     }
 
     /// Returns a vector of Ims received on login the bot/client is connected to.
     /// Only valid after login, otherwise None.
     pub fn get_start_ims(&self) -> Option<Vec<Im>> {
-        match self.start_info {
+        match self.state().start_info {
             Some(ref s) => Some(s.ims.clone()),
             None => None,
         }
     }
 
+    /// Renders message text such as `<@U123|bob> see <#C456>` in a human readable form,
+    /// with user and channel ids replaced by the names from the client's caches.
+    /// See `format::RenderMode` for the available output formats.
+    /// Only valid after login.
+    pub fn render_text(&self, text: &str, mode: format::RenderMode) -> String {
+        format::render(text,
+                       mode,
+                       |id| self.get_user(id).map(|u| u.name),
+                       |id| {
+                           let state = self.state();
+                           state.channels
+                                .iter()
+                                .find(|c| c.id == id)
+                                .map(|c| c.name.clone())
+                                .or_else(|| state.groups.iter().find(|g| g.id == id).map(|g| g.name.clone()))
+                       })
+    }
 
     ///Returns a unique identifier to be used in the 'id' field of a message
     ///sent to slack.
@@ -253,10 +406,12 @@ impl RtmClient {
     }
 
     /// Get a thread-safe message sender
+    /// Clone the client instead to resolve `#names` or call Web API methods from another thread.
     pub fn channel(&self) -> Option<Sender> {
-        self.outs.as_ref().cloned().map(|send| Sender {
+        self.outs().clone().map(|send| Sender {
             inner: send,
             msg_num: self.msg_num.clone(),
+            closing: self.closing.clone(),
         })
     }
 
@@ -269,13 +424,10 @@ impl RtmClient {
     /// use this method, and you will also need to retrieve a unique id for
     /// the message via RtmClient.get_msg_uid()
     /// Only valid after login.
-    pub fn send(&mut self, s: &str) -> Result<(), Error> {
-        let tx = match self.outs {
-            Some(ref tx) => tx,
-            None => return Err(Error::Internal(String::from("Failed to get tx!"))),
-        };
+    pub fn send(&self, s: &str) -> Result<(), Error> {
+        let tx = try!(self.sender());
         try!(tx.send(WsMessage::Text(s.to_string()))
-               .map_err(|err| Error::Internal(format!("{}", err))));
+               .map_err(|_| Error::NotConnected));
         Ok(())
     }
 
@@ -287,29 +439,38 @@ impl RtmClient {
     /// mean the message has been actually put on the wire yet.
     /// This method also handles getting a unique id and formatting the actual json
     /// sent.
+    /// The text is sent as is, use `format::escape` on text that may contain
+    /// `&`, `<` or `>` characters that aren't meant as slack formatting.
     /// Only valid after login.
     pub fn send_message(&self, chan: &str, msg: &str) -> Result<isize, Error> {
         let n = self.get_msg_uid();
 
-        let chan_id = match self.evaluate_channel_id(chan) {
-            Ok(id) => id,
-            _ => return Err(Error::Internal(String::from("Failed to get channel id")))
-        };
+        let chan_id = try!(self.evaluate_channel_id(chan));
 
         let msg_json = format!("{}", json::as_json(&msg));
         let mstr = format!(r#"{{"id": {},"type": "message", "channel": "{}","text": "{}"}}"#,
                            n,
                            chan_id,
                            &msg_json[1..msg_json.len() - 1]);
-        let tx = match self.outs {
-            Some(ref tx) => tx,
-            None => return Err(Error::Internal(String::from("Failed to get tx!"))),
-        };
+        let tx = try!(self.sender());
         try!(tx.send(WsMessage::Text(mstr))
-               .map_err(|err| Error::Internal(format!("{:?}", err))));
+               .map_err(|_| Error::NotConnected));
         Ok(n)
     }
 
+    /// Like send_message, but splits msg into several messages when it is longer than
+    /// the rtm limit of `format::RTM_MESSAGE_LIMIT` characters, see `format::split`.
+    /// The messages are sent in order, and their ids are returned in the same order.
+    /// Nothing is sent for a msg that is empty or only whitespace.
+    /// Only valid after login.
+    pub fn send_message_chunked(&self, chan: &str, msg: &str) -> Result<Vec<isize>, Error> {
+        let mut ids = Vec::new();
+        for chunk in format::split(msg, format::RTM_MESSAGE_LIMIT) {
+            ids.push(try!(self.send_message(chan, &chunk)));
+        }
+        Ok(ids)
+    }
+
     /// Marks connected client as being typing to a channel
     /// This is mostly used to signal to other peers that a message
     /// is being typed. Will have the server send a "user_typing" message to all the
@@ -318,22 +479,16 @@ impl RtmClient {
     pub fn send_typing(&self, chan: &str) -> Result<isize, Error> {
         let n = self.get_msg_uid();
 
-        let chan_id = match self.evaluate_channel_id(chan) {
-            Ok(id) => id,
-            _ => return Err(Error::Internal(String::from("Failed to get channel id")))
-        };
+        let chan_id = try!(self.evaluate_channel_id(chan));
 
         let mstr = format!(r#"{{"id": {}, "type": "typing", "channel": "{}"}}"#,
                            n,
                            chan_id);
 
-        let tx = match self.outs {
-            Some(ref tx) => tx,
-            None => return Err(Error::Internal(String::from("Failed to get tx!"))),
-        };
+        let tx = try!(self.sender());
 
         try!(tx.send(WsMessage::Text(mstr))
-             .map_err(|err| Error::Internal(format!("{:?}", err))));
+             .map_err(|_| Error::NotConnected));
         Ok(n)
     }
 
@@ -346,194 +501,167 @@ impl RtmClient {
         // websocket url
         let wss_url = try!(hyper::Url::parse(&start.url).map_err(|e| hyper::Error::Uri(e)));
 
-        // update id hashmaps
-        for ref channel in start.channels.iter() {
-            self.channel_ids.insert(channel.name.clone(), channel.id.clone());
-        }
-        for ref group in start.groups.iter() {
-            self.group_ids.insert(group.name.clone(), group.id.clone());
-        }
-        for ref user in start.users.iter() {
-            self.user_ids.insert(user.name.clone(), user.id.clone());
-        }
-        // update groups, users, channels:
-        self.groups = start.groups.clone();
-        self.channels = start.channels.clone();
-        self.users = start.users.clone();
-
-        // store rtm.Start data
-        self.start_info = Some(start);
-
-        // Do websocket connection request
-        let req = try!(websocket::client::Client::connect(wss_url.clone()));
+        // rtm.start doesn't include mpims. Tokens without the scope to list them can still
+        // log in, without any mpims cached, but other failures fail the login.
+        let mpims = match self.list_mpims() {
+            Ok(mpims) => mpims,
+            Err(ref err) if err.api_code() == Some(&ApiErrorCode::MissingScope) => Vec::new(),
+            Err(err) => return Err(err),
+        };
 
-        // Do websocket handshake.
-        let res = try!(req.send());
+        {
+            let mut state = self.state_mut();
+            // update id hashmaps
+            for ref channel in start.channels.iter() {
+                state.channel_ids.insert(channel.name.clone(), channel.id.clone());
+            }
+            for ref group in start.groups.iter() {
+                state.group_ids.insert(group.name.clone(), group.id.clone());
+            }
+            for ref user in start.users.iter() {
+                state.user_ids.insert(user.name.clone(), user.id.clone());
+            }
+            // update groups, users, channels:
+            state.groups = start.groups.clone();
+            state.channels = start.channels.clone();
+            state.users = start.users.clone();
+            state.mpims = mpims;
+
+            // store rtm.Start data
+            state.start_info = Some(start);
+        }
 
-        // Validate handshake
-        try!(res.validate());
+        let client = try!(connect_websocket(wss_url));
 
         // setup channels for passing messages
         let (tx, rx) = channel::<WsMessage>();
-        self.outs = Some(tx.clone());
-        Ok((res.begin(), rx))
+        self.set_outs(tx);
+        Ok((client, rx))
     }
 
-    /// Runs the message receive loop
+    /// Runs the message receive loop.
+    /// Returns Ok(()) when slack closes the connection normally, and an
+    /// `Error::ConnectionClosed` with the close code and reason for any other close.
     pub fn run<T: EventHandler>(&mut self, handler: &mut T, client: WsClient, rx: mpsc::Receiver<WsMessage>) -> Result<(), Error> {
         // for sending messages
-        let tx = match self.outs {
-            Some(ref mut tx) => tx.clone(),
-            None => return Err(Error::Internal(String::from("No tx!"))),
-        };
+        let tx = try!(self.sender());
 
-        let (mut sender, mut receiver) = client.split();
+        let (sender, mut receiver) = client.split();
+        // before the sender thread is spawned, so that there is nothing to shut down yet
+        try!(set_read_timeout(&mut receiver));
 
         handler.on_connect(self);
-        // websocket send loop
-        // We used thread::scoped previously but it is no longer stable...
-        let child = thread::spawn(move || -> () {
-            loop {
-                let msg = match rx.recv() {
-                    Ok(m) => m,
-                    Err(_) => {
-                        // if we had an error receiving, shutdown the sender
-                        // and receiver so that we return.
-                        match sender.shutdown_all() {
-                            Ok(_) => {},
-                            Err(err) => panic!(err),
-                        };
-                        return;
-                    }
-                };
-
-                match msg {
-                    WsMessage::Close => {
-                        drop(rx);
-                        // websocket is closed, so shutdown the sender and receiver so that we
-                        // return.
-                        match sender.shutdown_all() {
-                            Ok(_) => {}
-                            Err(err) => panic!(err),
-                        };
-                        return;
-                    },
-                    WsMessage::Text(text) => {
-                        let message = WebSocketMessage::text(text);
-                        match sender.send_message(&message) {
-                            Ok(_) => {},
-                            Err(_) => {
-                                // if we had an error sending, shutdown the sender
-                                // and receiver so that we return.
-                                match sender.shutdown_all() {
-                                    Ok(_) => {},
-                                    Err(err) => panic!(err),
-                                };
-                                return;
-                            }
-                        }
-                    },
-                    WsMessage::Pong(data) => {
-                        let message = WebSocketMessage::pong(data.as_bytes());
-                        match sender.send_message(&message) {
-                            Ok(_) => {},
-                            Err(_) => {
-                                // if we had an error sending, shutdown the sender
-                                // and receiver so that we return.
-                                match sender.shutdown_all() {
-                                    Ok(_) => {},
-                                    Err(err) => panic!(err),
-                                };
-                                return;
-                            }
-                        }
-                    }
-                };
-            }
-        });
+        let (errors_tx, errors) = channel::<Error>();
+        let child = spawn_sender(sender, rx, errors_tx);
+        self.run_receiver(handler, &mut receiver, &tx, child, &errors)
+    }
 
-        // set receive timeout long enough for slack ping
-        {
-            let read_timeout = std::time::Duration::from_secs(70);
-            let mut ws_stream = receiver.get_mut().get_mut();
-            let tcp_stream: &mut std::net::TcpStream = match ws_stream {
-                &mut WebSocketStream::Tcp(ref mut s) => s,
-                &mut WebSocketStream::Ssl(ref mut s) => s.get_mut(),
-            };
-            try!(tcp_stream.set_read_timeout(Some(read_timeout)));
+    /// Runs the receive loop until the connection is closed or fails, then shuts down
+    /// the sender and receiver and joins the sender thread. Returns the error of the
+    /// sender thread if it had one, as that is why the connection went down, and Ok(()) if
+    /// the connection was shut down locally.
+    fn run_receiver<T: EventHandler, I: Incoming>(&mut self,
+                                                  handler: &mut T,
+                                                  incoming: &mut I,
+                                                  tx: &mpsc::Sender<WsMessage>,
+                                                  child: thread::JoinHandle<()>,
+                                                  errors: &mpsc::Receiver<Error>)
+                                                  -> Result<(), Error> {
+        let result = self.receive_events(handler, incoming, tx);
+        let _ = tx.send(WsMessage::Close);
+        incoming.shutdown();
+        match join_sender(child, errors) {
+            Some(err) => Err(err),
+            // the sender thread shuts down the connection, which can fail the receive
+            None if self.closed_locally() => Ok(()),
+            None => result,
         }
+    }
 
-        // receive loop
+    /// Passes the messages received to handler until slack closes the connection or
+    /// receiving fails.
+    fn receive_events<T: EventHandler, I: Incoming>(&mut self,
+                                                    handler: &mut T,
+                                                    incoming: &mut I,
+                                                    tx: &mpsc::Sender<WsMessage>)
+                                                    -> Result<(), Error> {
         loop {
-            // receive
-            let message_result : WebSocketResult<WebSocketMessage> = receiver.recv_message();
-            // unwrap result
-            let message : WebSocketMessage = match message_result {
+            let message = match incoming.receive() {
                 Ok(message) => message,
-                Err(err) => {
-                    // If error is equivalent of EAGAIN, just loop
-                    if let WebSocketError::IoError(ref io_err) = err {
-                        if io_err.kind() == io::ErrorKind::WouldBlock {
-                            continue
-                        }
-                    }
-
-                    // shutdown sender and receiver, then join the child thread
-                    // and return an error.
-                    let _ = tx.send(WsMessage::Close);
-                    let _ = receiver.shutdown_all();
-                    let _ = child.join();
-                    return Err(Error::Internal(format!("{:?}", err)));
-                }
+                Err(err) => return Err(websocket_error(err)),
             };
             // handle the message
             match message.opcode {
                 WsType::Text => {
-                    let raw_string : String = try!(String::from_utf8(message.payload.into_owned()));
+                    let raw_string = match String::from_utf8(message.payload.to_vec()) {
+                        Ok(raw_string) => raw_string,
+                        Err(err) => {
+                            // like json that can't be decoded, this doesn't break the connection
+                            let raw_string = String::from_utf8_lossy(&message.payload).into_owned();
+                            handler.on_event(self, Err(Error::Utf8(err)), &raw_string);
+                            continue;
+                        }
+                    };
                     match json::decode(&raw_string) {
-                        Ok(event) => handler.on_event(self, Ok(event), &raw_string),
+                        Ok(event) => {
+                            self.update_caches(&event);
+                            handler.on_event(self, Ok(event), &raw_string)
+                        }
                         Err(err) => handler.on_event(self, Err(Error::JsonDecode(err)), &raw_string),
                     }
                 }
                 WsType::Ping => {
                     handler.on_ping(self);
-                    let raw_string : String = try!(String::from_utf8(message.payload.into_owned()));
-                    match tx.send(WsMessage::Pong(raw_string)) {
-                        Ok(_) => {}
-                        Err(err) => {
-                            // shutdown sender and receiver, then join the child thread
-                            // and return an error.
-                            let _ = receiver.shutdown_all();
-                            let _ = child.join();
-                            return Err(Error::Internal(format!("{:?}", err)));
-                        }
+                    let raw_string = try!(String::from_utf8(message.payload.into_owned()));
+                    if tx.send(WsMessage::Pong(raw_string)).is_err() {
+                        // the sender thread ended because sending failed
+                        return Err(sender_closed());
                     }
                 }
                 WsType::Close => {
-                    handler.on_close(self);
-                    match tx.send(WsMessage::Close) {
-                        Ok(_) => {}
-                        Err(err) => {
-                            // shutdown sender and receiver, then join the child thread
-                            // and return an error.
-                            let _ = receiver.shutdown_all();
-                            let _ = child.join();
-                            return Err(Error::Internal(format!("{:?}", err)));
-                        }
-                    }
-                    // close the sender and receiver
-                    let _ = receiver.shutdown_all();
-                    // join the child thread, return error if the child thread paniced
-                    return match child.join() {
-                        Ok(_) => Ok(()),
-                        Err(err) => Err(Error::Internal(format!("child thread error in run: {:?}", err)))
-                    };
+                    let (code, reason) = close_status(&message.payload);
+                    handler.on_close(self, code, &reason);
+                    return close_result(code, reason, self.closed_locally());
                 }
                 _ => {}
             }
         }
     }
 
+    /// Keeps the cached conversations up to date with events that change them.
+    fn update_caches(&self, event: &Event) {
+        match *event {
+            Event::MpimJoined { ref channel } => {
+                let mut state = self.state_mut();
+                if !state.mpims.iter().any(|m| m.id == channel.id) {
+                    state.mpims.push(channel.clone());
+                }
+            }
+            Event::MpimOpen { ref channel, .. } => self.set_mpim_open(channel, true),
+            Event::MpimClose { ref channel, .. } => self.set_mpim_open(channel, false),
+            _ => {}
+        }
+    }
+
+    /// Sets whether the cached mpim with id is open. A closed mpim is still an mpim, so it
+    /// stays cached, and an mpim that isn't cached yet is added with just its id.
+    fn set_mpim_open(&self, id: &str, open: bool) {
+        let mut state = self.state_mut();
+        if let Some(mpim) = state.mpims.iter_mut().find(|m| m.id == id) {
+            mpim.is_open = Some(open);
+            return;
+        }
+        state.mpims.push(mpim::Mpim {
+            id: id.to_string(),
+            name: String::new(),
+            is_mpim: Some(true),
+            created: None,
+            creator: None,
+            members: None,
+            is_open: Some(open),
+        });
+    }
+
     /// Runs the main loop for the client after logging in to slack,
     /// returns an error if the process fails at any point, or an Ok(()) on successful
     /// close.
@@ -550,19 +678,24 @@ impl RtmClient {
     }
 
 
-    /// Shutdown `RtmClient`
+    /// Shutdown `RtmClient`, which makes `run` return Ok(())
     pub fn shutdown(&self) -> Result<(), Error> {
-        match self.outs {
-            Some(ref tx) => {
-                tx.send(WsMessage::Close)
-                    .map_err(|_| Error::Internal("Error sending shutdown message".into()))
-            }
-            None => Err(Error::Internal("Cannot shutdown without a sender".into())),
-        }
+        let tx = try!(self.sender());
+        self.closing.store(true, Ordering::SeqCst);
+        tx.send(WsMessage::Close)
+          .map_err(|_| Error::NotConnected)
+    }
+
+    /// Shutdown `RtmClient` once the queued messages are sent, see `Sender::shutdown_gracefully`.
+    pub fn shutdown_gracefully(&self, timeout: Duration) -> Result<(), Error> {
+        let tx = try!(self.sender());
+        self.closing.store(true, Ordering::SeqCst);
+        tx.send(WsMessage::CloseGracefully(timeout))
+          .map_err(|_| Error::NotConnected)
     }
 
     /// Uses https://api.slack.com/methods/users.list to get a list of users
-    pub fn list_users(&mut self) -> Result<Vec<User>, Error> {
+    pub fn list_users(&self) -> Result<Vec<User>, Error> {
         let client = hyper::Client::new();
         let data = try!(api::users::list(&client, &self.token, None));
 
@@ -570,7 +703,7 @@ impl RtmClient {
     }
 
     /// Uses https://api.slack.com/methods/channels.list to get a list of channels
-    pub fn list_channels(&mut self) -> Result<Vec<Channel>, Error> {
+    pub fn list_channels(&self) -> Result<Vec<Channel>, Error> {
         let client = hyper::Client::new();
         let data = try!(api::channels::list(&client, &self.token, None));
 
@@ -578,7 +711,7 @@ impl RtmClient {
     }
 
     /// Uses https://api.slack.com/methods/groups.list to get a list of groups
-    pub fn list_groups(&mut self) -> Result<Vec<Group>, Error> {
+    pub fn list_groups(&self) -> Result<Vec<Group>, Error> {
         let client = hyper::Client::new();
         let data = try!(api::groups::list(&client, &self.token, None));
 
@@ -586,45 +719,48 @@ impl RtmClient {
     }
 
     /// Uses https://api.slack.com/methods/users.list to update users
-    pub fn update_users(&mut self) -> Result<Vec<User>, Error> {
+    pub fn update_users(&self) -> Result<Vec<User>, Error> {
         let users = try!(self.list_users());
 
+        let mut state = self.state_mut();
         // update user id map
-        self.user_ids.clear();
+        state.user_ids.clear();
         for ref user in users.iter() {
-            self.user_ids.insert(user.name.clone(), user.id.clone());
+            state.user_ids.insert(user.name.clone(), user.id.clone());
         }
         // update users
-        self.users = users.clone();
+        state.users = users.clone();
 
         Ok(users)
     }
 
     /// Uses https://api.slack.com/methods/channels.list to update channels
-    pub fn update_channels(&mut self) -> Result<Vec<Channel>, Error> {
+    pub fn update_channels(&self) -> Result<Vec<Channel>, Error> {
         let channels = try!(self.list_channels());
 
+        let mut state = self.state_mut();
         // update channel id map
-        self.channel_ids.clear();
+        state.channel_ids.clear();
         for ref channel in channels.iter() {
-            self.channel_ids.insert(channel.name.clone(), channel.id.clone());
+            state.channel_ids.insert(channel.name.clone(), channel.id.clone());
         }
         // update users
-        self.channels = channels.clone();
+        state.channels = channels.clone();
 
         Ok(channels)
     }
 
     /// Uses https://api.slack.com/methods/groups.list to update groups
-    pub fn update_groups(&mut self) -> Result<Vec<Group>, Error> {
+    pub fn update_groups(&self) -> Result<Vec<Group>, Error> {
         let groups = try!(self.list_groups());
+        let mut state = self.state_mut();
         // update group id map
-        self.group_ids.clear();
+        state.group_ids.clear();
         for ref group in groups.iter() {
-            self.group_ids.insert(group.name.clone(), group.id.clone());
+            state.group_ids.insert(group.name.clone(), group.id.clone());
         }
         // update users
-        self.groups = groups.clone();
+        state.groups = groups.clone();
         Ok(groups)
     }
 
@@ -632,20 +768,11 @@ impl RtmClient {
     /// json_payload can be a json formatted action or simple text that will be posted as a message.
     /// See https://api.slack.com/docs/formatting
     pub fn post_message(&self, channel: &str, json_payload: &str, attachments: Option<&str>) -> Result<api::chat::PostMessageResponse, Error> {
-        // fixup the channel id if channel is: `#<channel>`
-        let chan_id = match channel.starts_with("#") {
-            true => {
-                match self.get_channel_id(&channel[1..]) {
-                    Some(s) => &(s[..]),
-                    None => return Err(Error::Api(String::from("start_info is invalid, need to login first"))),
-                }
-            }
-            false => channel,
-        };
+        let chan_id = try!(self.evaluate_channel_id(channel));
         let client = hyper::Client::new();
         api::chat::post_message(&client,
                                 &self.token,
-                                chan_id,
+                                &chan_id,
                                 json_payload,
                                 None,
                                 Some(true),
@@ -658,108 +785,95 @@ impl RtmClient {
                                 None).map_err(|e| e.into())
     }
 
+    /// Like post_message, but splits json_payload into several messages when it is longer
+    /// than the `format::WEB_MESSAGE_LIMIT` characters accepted by chat.postMessage.
+    /// The messages are posted in order and attachments are only sent with the last one.
+    /// A json_payload that is empty or only whitespace is only posted with attachments.
+    pub fn post_message_chunked(&self, channel: &str, json_payload: &str, attachments: Option<&str>) -> Result<Vec<api::chat::PostMessageResponse>, Error> {
+        let chunks = format::split(json_payload, format::WEB_MESSAGE_LIMIT);
+        if chunks.is_empty() {
+            // slack only accepts a message without text if it has attachments
+            return match attachments {
+                Some(_) => Ok(vec![try!(self.post_message(channel, json_payload, attachments))]),
+                None => Ok(Vec::new()),
+            };
+        }
+        let last = chunks.len() - 1;
+        let mut responses = Vec::new();
+        for (i, chunk) in chunks.iter().enumerate() {
+            let attachments = if i == last { attachments } else { None };
+            responses.push(try!(self.post_message(channel, chunk, attachments)));
+        }
+        Ok(responses)
+    }
+
     /// Wraps https://api.slack.com/methods/chat.delete to delete a message
     /// See the slack api docs for timestamp formatting.
     pub fn delete_message(&self, channel: &str, timestamp: &str) -> Result<api::chat::DeleteResponse, Error> {
-        // fixup the channel id if channel is: `#<channel>`
-        let chan_id = match channel.starts_with("#") {
-            true => {
-                match self.get_channel_id(&channel[1..]) {
-                    Some(s) => &(s[..]),
-                    None => return Err(Error::Api(String::from("start_info is invalid, need to login first"))),
-                }
-            }
-            false => channel,
-        };
+        let chan_id = try!(self.evaluate_channel_id(channel));
         let client = hyper::Client::new();
-        api::chat::delete(&client, &self.token, timestamp, chan_id).map_err(|e| e.into())
+        api::chat::delete(&client, &self.token, timestamp, &chan_id).map_err(|e| e.into())
     }
 
-    /// Wraps https://api.slack.com/methods/channels.mark to set the read cursor in a channel
+    /// Wraps https://api.slack.com/methods/channels.mark, https://api.slack.com/methods/groups.mark
+    /// or https://api.slack.com/methods/im.mark, depending on the type of channel, to set the
+    /// read cursor in a channel.
+    /// if channel starts with a # then it will be looked up in the channels and groups.
     /// See the slack api docs for timestamp formatting.
     pub fn mark(&self, channel: &str, timestamp: &str) -> Result<api::channels::MarkResponse, Error> {
-        // fixup the channel id if channel is: `#<channel>`
-        let chan_id = match channel.starts_with("#") {
-            true => {
-                match self.get_channel_id(&channel[1..]) {
-                    Some(s) => &(s[..]),
-                    None => return Err(Error::Api(String::from("start_info is invalid, need to login first"))),
-                }
-            }
-            false => channel,
-        };
+        let chan_id = try!(self.evaluate_channel_id(channel));
+        let kind = try!(self.channel_kind(&chan_id));
         let client = hyper::Client::new();
-        api::channels::mark(&client, &self.token, chan_id, timestamp).map_err(|e| e.into())
+        try!(web::call_json(&client,
+                            &format!("{}.mark", kind.api_prefix()),
+                            &[("token", &self.token[..]), ("channel", &chan_id[..]), ("ts", timestamp)]));
+        Ok(api::channels::MarkResponse)
     }
 
-    /// Wraps https://api.slack.com/methods/channels.setTopic
-    /// if channel starts with a # then it will be looked up with get_channel_id
-    /// topic will be json escaped.
+    /// Wraps https://api.slack.com/methods/channels.setTopic or
+    /// https://api.slack.com/methods/groups.setTopic, depending on the type of channel,
+    /// and returns the new topic.
+    /// if channel starts with a # then it will be looked up in the channels and groups.
     pub fn set_topic(&self, channel: &str, topic: &str) -> Result<api::channels::SetTopicResponse, Error> {
-        // fixup the channel id if channel is: `#<channel>`
-        let chan_id = match channel.starts_with("#") {
-            true => {
-                match self.get_channel_id(&channel[1..]) {
-                    Some(s) => &(s[..]),
-                    None => return Err(Error::Api(String::from("start_info is invalid, need to login first"))),
-                }
-            }
-            false => channel,
-        };
-        // this will json format the string, which should escape it,
-        // we'll need to slice out the quotes around it afterwards
-        let escaped_topic = format!("{}", json::as_json(&topic));
-        let client = hyper::Client::new();
-        api::channels::set_topic(&client,
-                                 &self.token,
-                                 chan_id,
-                                 &escaped_topic[1..escaped_topic.len() - 1]).map_err(|e| e.into())
+        let topic = try!(self.set_channel_info(channel, "setTopic", "topic", topic));
+        Ok(api::channels::SetTopicResponse { topic: topic })
     }
 
-    /// Wraps https://api.slack.com/methods/channels.setPurpose
-    /// if channel starts with a # then it will be looked up with get_channel_id
-    /// purpose will be json escaped.
+    /// Wraps https://api.slack.com/methods/channels.setPurpose or
+    /// https://api.slack.com/methods/groups.setPurpose, depending on the type of channel,
+    /// and returns the new purpose.
+    /// if channel starts with a # then it will be looked up in the channels and groups.
     pub fn set_purpose(&self, channel: &str, purpose: &str) -> Result<api::channels::SetPurposeResponse, Error> {
-        // fixup the channel id if channel is: `#<channel>`
-        let chan_id = match channel.starts_with("#") {
-            true => {
-                match self.get_channel_id(&channel[1..]) {
-                    Some(s) => &(s[..]),
-                    None => return Err(Error::Api(String::from("start_info is invalid, need to login first"))),
-                }
-            }
-            false => channel,
-        };
-        // this will json format the string, which should escape it,
-        // we'll need to slice out the quotes around it afterwards
-        let escaped_purpose = format!("{}", json::as_json(&purpose));
+        let purpose = try!(self.set_channel_info(channel, "setPurpose", "purpose", purpose));
+        Ok(api::channels::SetPurposeResponse { purpose: purpose })
+    }
+
+    /// Calls the setTopic or setPurpose method for the type of channel,
+    /// field is both the name of the param and the field of the response.
+    fn set_channel_info(&self, channel: &str, method: &str, field: &str, value: &str) -> Result<String, Error> {
+        let chan_id = try!(self.evaluate_channel_id(channel));
+        let kind = try!(self.channel_kind(&chan_id));
+        if kind == ChannelKind::Im || kind == ChannelKind::Mpim {
+            return Err(Error::Internal(format!("cannot {} of a direct message channel", method)));
+        }
         let client = hyper::Client::new();
-        api::channels::set_purpose(&client,
-                                   &self.token,
-                                   chan_id,
-                                   &escaped_purpose[1..escaped_purpose.len() - 1]).map_err(|e| e.into())
+        let res = try!(web::call_json(&client,
+                                      &format!("{}.{}", kind.api_prefix(), method),
+                                      &[("token", &self.token[..]), ("channel", &chan_id[..]), (field, value)]));
+        Ok(res.find(field).and_then(|v| v.as_string()).unwrap_or(value).to_string())
     }
 
     /// Wraps https://api.slack.com/methods/reactions.add to add an emoji reaction to a message
     /// if channel starts with a # then it will be looked up with get_channel_id
     pub fn add_reaction_timestamp(&self, emoji_name: &str, channel: &str, timestamp: &str) -> Result<api::reactions::AddResponse, Error> {
-        // fixup the channel id if channel is: `#<channel>`
-        let chan_id = match channel.starts_with("#") {
-            true => {
-                match self.get_channel_id(&channel[1..]) {
-                    Some(s) => &(s[..]),
-                    None => return Err(Error::Api(String::from("start_info is invalid, need to login first"))),
-                }
-            }
-            false => channel,
-        };
+        let chan_id = try!(self.evaluate_channel_id(channel));
         let client = hyper::Client::new();
         api::reactions::add(&client,
                             &self.token,
                             emoji_name,
                             None,
                             None,
-                            Some(chan_id),
+                            Some(&chan_id),
                             Some(timestamp)).map_err(|e| e.into())
     }
 
@@ -791,21 +905,12 @@ impl RtmClient {
     /// json_payload can be a json formatted action or simple text that will be posted as a message.
     /// See https://api.slack.com/docs/formatting
     pub fn update_message(&self, channel: &str, timestamp: &str, json_payload: &str, attachments: Option<&str>) -> Result<api::chat::UpdateResponse, Error> {
-        // fixup the channel id if channel is: `#<channel>`
-        let chan_id = match channel.starts_with("#") {
-            true => {
-                match self.get_channel_id(&channel[1..]) {
-                    Some(s) => &(s[..]),
-                    None => return Err(Error::Api(String::from("start_info is invalid, need to login first"))),
-                }
-            }
-            false => channel,
-        };
+        let chan_id = try!(self.evaluate_channel_id(channel));
         let client = hyper::Client::new();
         api::chat::update(&client,
                           &self.token,
                           timestamp,
-                          chan_id,
+                          &chan_id,
                           json_payload,
                           attachments,
                           None,
@@ -876,3 +981,457 @@ impl RtmClient {
         api::im::mark(&client, &self.token, channel_id, timestamp).map_err(|e| e.into())
     }
 }
+
+/// Connects to a websocket url and completes the handshake.
+fn connect_websocket(wss_url: hyper::Url) -> Result<WsClient, Error> {
+    // Do websocket connection request
+    let req = try!(websocket::client::Client::connect(wss_url));
+
+    // Do websocket handshake.
+    let res = try!(req.send().map_err(Error::HandshakeFailed));
+
+    // Validate handshake
+    try!(res.validate().map_err(Error::HandshakeFailed));
+
+    Ok(res.begin())
+}
+
+/// Spawns the thread that sends the messages passed in through rx over the websocket.
+/// The thread ends when a `WsMessage::Close` is received or sending fails, passing the
+/// error to errors for the receive loop to return. After a `WsMessage::CloseGracefully`
+/// the error is `Error::Timeout` if slack doesn't close the connection in time.
+fn spawn_sender(mut sender: WsSender<WebSocketStream>,
+                rx: mpsc::Receiver<WsMessage>,
+                errors: mpsc::Sender<Error>)
+                -> thread::JoinHandle<()> {
+    // We used thread::scoped previously but it is no longer stable...
+    thread::spawn(move || {
+        let result = send_messages(&mut sender, &rx);
+        drop(rx);
+        // shutdown the sender and receiver so that the receive loop returns. This fails
+        // if the connection is already gone, which the receive loop finds out as well.
+        let _ = sender.shutdown_all();
+        if let Err(err) = result {
+            let _ = errors.send(err);
+        }
+    })
+}
+
+/// Sends the messages passed in through rx until a `WsMessage::Close` is received, every
+/// `mpsc::Sender` is gone or sending fails. Messages are sent in the order they were
+/// queued, so text queued before a `WsMessage::CloseGracefully` goes out before the close.
+fn send_messages<S: WsSenderTrait>(sender: &mut S, rx: &mpsc::Receiver<WsMessage>) -> Result<(), Error> {
+    loop {
+        let msg = match rx.recv() {
+            Ok(m) => m,
+            Err(_) => return Ok(()),
+        };
+
+        match msg {
+            WsMessage::Close => return Ok(()),
+            WsMessage::CloseGracefully(timeout) => {
+                // the messages queued before this one have been sent, so send a close
+                // frame and wait for the receive loop to pass on slack's close.
+                let deadline = Instant::now() + timeout;
+                try!(sender.send_message(&WebSocketMessage::close()).map_err(websocket_error));
+                return if wait_for_close(rx, deadline) {
+                    Ok(())
+                } else {
+                    Err(Error::Timeout)
+                };
+            }
+            WsMessage::Text(text) => {
+                let message = WebSocketMessage::text(text);
+                try!(sender.send_message(&message).map_err(websocket_error));
+            }
+            WsMessage::Pong(data) => {
+                let message = WebSocketMessage::pong(data.as_bytes());
+                try!(sender.send_message(&message).map_err(websocket_error));
+            }
+        }
+    }
+}
+
+/// Joins the sender thread, returning the error it ended with, if any.
+fn join_sender(child: thread::JoinHandle<()>, errors: &mpsc::Receiver<Error>) -> Option<Error> {
+    match child.join() {
+        Ok(_) => errors.try_recv().ok(),
+        Err(_) => Some(Error::SenderThreadPanicked),
+    }
+}
+
+/// Waits until deadline for the `WsMessage::Close` passed on by the receive loop when slack
+/// closes the connection. Returns false if the deadline passed first.
+fn wait_for_close(rx: &mpsc::Receiver<WsMessage>, deadline: Instant) -> bool {
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            return false;
+        }
+        match rx.recv_timeout(deadline - now) {
+            Ok(WsMessage::Close) |
+            Err(RecvTimeoutError::Disconnected) => return true,
+            // nothing can be sent after the close frame
+            Ok(_) => {}
+            Err(RecvTimeoutError::Timeout) => return false,
+        }
+    }
+}
+
+/// How long to wait for a message before giving up on the connection, in seconds.
+/// Long enough for slack pings.
+const READ_TIMEOUT: u64 = 70;
+
+/// Sets a receive timeout of `READ_TIMEOUT` on the websocket.
+fn set_read_timeout(receiver: &mut WsReceiver<WebSocketStream>) -> Result<(), Error> {
+    let read_timeout = Duration::from_secs(READ_TIMEOUT);
+    let mut ws_stream = receiver.get_mut().get_mut();
+    let tcp_stream: &mut std::net::TcpStream = match ws_stream {
+        &mut WebSocketStream::Tcp(ref mut s) => s,
+        &mut WebSocketStream::Ssl(ref mut s) => s.get_mut(),
+    };
+    try!(tcp_stream.set_read_timeout(Some(read_timeout)));
+    Ok(())
+}
+
+/// Receives the next message from the websocket, retrying while none is ready.
+/// Fails with a `TimedOut` io error once nothing was received for `READ_TIMEOUT` seconds,
+/// as on unix the read timeout surfaces as `WouldBlock`.
+fn receive(receiver: &mut WsReceiver<WebSocketStream>) -> WebSocketResult<WebSocketMessage<'static>> {
+    let started = Instant::now();
+    loop {
+        match receiver.recv_message() {
+            // If error is equivalent of EAGAIN, loop until the read timeout has passed
+            Err(WebSocketError::IoError(ref io_err)) if io_err.kind() == io::ErrorKind::WouldBlock => {
+                if started.elapsed() >= Duration::from_secs(READ_TIMEOUT) {
+                    return Err(WebSocketError::IoError(io::Error::new(io::ErrorKind::TimedOut,
+                                                                      "no message received in time")));
+                }
+            }
+            result => return result,
+        }
+    }
+}
+
+/// The receiving half of a websocket connection, as read by the receive loop.
+trait Incoming {
+    /// Receives the next message, see `receive`.
+    fn receive(&mut self) -> WebSocketResult<WebSocketMessage<'static>>;
+
+    /// Shuts down the connection, which ends the sender thread if it is still sending.
+    fn shutdown(&mut self);
+}
+
+impl Incoming for WsReceiver<WebSocketStream> {
+    fn receive(&mut self) -> WebSocketResult<WebSocketMessage<'static>> {
+        receive(self)
+    }
+
+    fn shutdown(&mut self) {
+        let _ = self.shutdown_all();
+    }
+}
+
+/// Returns the error for a failed receive or send.
+fn websocket_error(err: WebSocketError) -> Error {
+    match err {
+        WebSocketError::NoDataAvailable => {
+            Error::ConnectionClosed {
+                code: None,
+                reason: String::from("connection closed without a close frame"),
+            }
+        }
+        WebSocketError::IoError(ref io_err) if io_err.kind() == io::ErrorKind::TimedOut => Error::Timeout,
+        WebSocketError::IoError(ref io_err) if io_err.kind() == io::ErrorKind::ConnectionReset ||
+                                               io_err.kind() == io::ErrorKind::ConnectionAborted ||
+                                               io_err.kind() == io::ErrorKind::BrokenPipe ||
+                                               io_err.kind() == io::ErrorKind::UnexpectedEof => {
+            Error::ConnectionClosed {
+                code: None,
+                reason: format!("{}", io_err),
+            }
+        }
+        err => Error::WebSocket(err),
+    }
+}
+
+/// Reads the code and reason of a close frame payload: a 2 byte big-endian code, followed
+/// by the utf8 reason.
+fn close_status(payload: &[u8]) -> (Option<u16>, String) {
+    if payload.len() < 2 {
+        return (None, String::new());
+    }
+    let code = (payload[0] as u16) << 8 | payload[1] as u16;
+    (Some(code), String::from_utf8_lossy(&payload[2..]).into_owned())
+}
+
+/// Returns Ok(()) for a normal close or one that was started locally, and an
+/// `Error::ConnectionClosed` for any other.
+fn close_result(code: Option<u16>, reason: String, locally: bool) -> Result<(), Error> {
+    match code {
+        _ if locally => Ok(()),
+        None | Some(1000) => Ok(()),
+        _ => {
+            Err(Error::ConnectionClosed {
+                code: code,
+                reason: reason,
+            })
+        }
+    }
+}
+
+/// Returns the error for a message that could not be passed to the sender thread,
+/// which only ends early when the connection is gone.
+fn sender_closed() -> Error {
+    Error::ConnectionClosed {
+        code: None,
+        reason: String::from("the websocket could not be written to"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc::{channel, Receiver};
+    use std::thread::{self, JoinHandle};
+    use std::time::{Duration, Instant};
+
+    use websocket::message::Type as WsType;
+    use websocket::result::{WebSocketError, WebSocketResult};
+    use websocket::ws::dataframe::DataFrame;
+
+    use super::{close_result, close_status, join_sender, send_messages, wait_for_close, EventHandler, Incoming,
+                RtmClient, WebSocketMessage, WsMessage, WsSenderTrait};
+    use error::Error;
+    use events::Event;
+
+    const TEXT: u8 = 1;
+    const CLOSE: u8 = 8;
+
+    /// Records the opcode and payload of the frames sent through it.
+    struct RecordingSender {
+        frames: Vec<(u8, Vec<u8>)>,
+    }
+
+    impl WsSenderTrait for RecordingSender {
+        fn send_dataframe<D>(&mut self, dataframe: &D) -> WebSocketResult<()>
+            where D: DataFrame
+        {
+            self.frames.push((dataframe.opcode(), dataframe.payload().into_owned()));
+            Ok(())
+        }
+    }
+
+    /// Hands out the messages it was created with, then fails as if the connection was gone.
+    struct ScriptedIncoming {
+        messages: VecDeque<WebSocketMessage<'static>>,
+        shut_down: bool,
+    }
+
+    impl Incoming for ScriptedIncoming {
+        fn receive(&mut self) -> WebSocketResult<WebSocketMessage<'static>> {
+            self.messages.pop_front().ok_or(WebSocketError::NoDataAvailable)
+        }
+
+        fn shutdown(&mut self) {
+            self.shut_down = true;
+        }
+    }
+
+    /// Counts the events it is called with that failed to be read.
+    struct CountingHandler {
+        errors: usize,
+        closes: usize,
+    }
+
+    impl EventHandler for CountingHandler {
+        fn on_event(&mut self, _cli: &mut RtmClient, event: Result<Event, Error>, _raw_json: &str) {
+            if event.is_err() {
+                self.errors += 1;
+            }
+        }
+
+        fn on_ping(&mut self, _cli: &mut RtmClient) {}
+
+        fn on_close(&mut self, _cli: &mut RtmClient, _code: Option<u16>, _reason: &str) {
+            self.closes += 1;
+        }
+
+        fn on_connect(&mut self, _cli: &mut RtmClient) {}
+    }
+
+    /// Spawns a sender thread like `spawn_sender` that sends to a `RecordingSender`,
+    /// returning it with its errors and whether it has ended.
+    fn spawn_recording_sender(rx: Receiver<WsMessage>) -> (JoinHandle<()>, Receiver<Error>, Arc<AtomicBool>) {
+        let (errors_tx, errors) = channel();
+        let ended = Arc::new(AtomicBool::new(false));
+        let sender_ended = ended.clone();
+        let child = thread::spawn(move || {
+            let mut sender = RecordingSender { frames: Vec::new() };
+            if let Err(err) = send_messages(&mut sender, &rx) {
+                let _ = errors_tx.send(err);
+            }
+            sender_ended.store(true, Ordering::SeqCst);
+        });
+        (child, errors, ended)
+    }
+
+    #[test]
+    fn invalid_utf8_text_is_passed_to_handler() {
+        let (tx, rx) = channel();
+        let (child, errors, ended) = spawn_recording_sender(rx);
+
+        let mut invalid = WebSocketMessage::binary(vec![0xff, 0xfe, 0xfd]);
+        invalid.opcode = WsType::Text;
+        let mut incoming = ScriptedIncoming {
+            messages: vec![invalid, WebSocketMessage::close()].into_iter().collect(),
+            shut_down: false,
+        };
+        let mut handler = CountingHandler { errors: 0, closes: 0 };
+        let mut cli = RtmClient::new("token");
+        assert!(cli.run_receiver(&mut handler, &mut incoming, &tx, child, &errors).is_ok());
+        assert_eq!(handler.errors, 1);
+        assert_eq!(handler.closes, 1);
+        assert!(incoming.shut_down);
+        assert!(ended.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn invalid_utf8_ping_joins_sender() {
+        let (tx, rx) = channel();
+        let (child, errors, ended) = spawn_recording_sender(rx);
+
+        let mut ping = WebSocketMessage::binary(vec![0xff]);
+        ping.opcode = WsType::Ping;
+        let mut incoming = ScriptedIncoming {
+            messages: vec![ping].into_iter().collect(),
+            shut_down: false,
+        };
+        let mut handler = CountingHandler { errors: 0, closes: 0 };
+        let mut cli = RtmClient::new("token");
+        match cli.run_receiver(&mut handler, &mut incoming, &tx, child, &errors) {
+            Err(Error::Utf8(_)) => {}
+            _ => panic!("Invalid ping payload was not an error."),
+        }
+        assert!(incoming.shut_down);
+        assert!(ended.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn parse_close_status() {
+        assert_eq!(close_status(b""), (None, String::new()));
+        assert_eq!(close_status(b"\x03\xe8"), (Some(1000), String::new()));
+        assert_eq!(close_status(b"\x03\xe9server restart"), (Some(1001), String::from("server restart")));
+    }
+
+    #[test]
+    fn abnormal_closes_are_errors() {
+        assert!(close_result(None, String::new(), false).is_ok());
+        assert!(close_result(Some(1000), String::new(), false).is_ok());
+        match close_result(Some(1001), String::from("going away"), false) {
+            Err(Error::ConnectionClosed { code: Some(1001), ref reason }) => assert_eq!(reason, "going away"),
+            _ => panic!("Close was not an error."),
+        }
+    }
+
+    #[test]
+    fn graceful_close_waits_for_close() {
+        let (tx, rx) = channel();
+        tx.send(WsMessage::Text(String::from("dropped"))).unwrap();
+        tx.send(WsMessage::Close).unwrap();
+        assert!(wait_for_close(&rx, Instant::now() + Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn graceful_close_times_out() {
+        let (tx, rx) = channel();
+        tx.send(WsMessage::Text(String::from("dropped"))).unwrap();
+        assert!(!wait_for_close(&rx, Instant::now() + Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn graceful_close_sends_queued_text_first() {
+        let (tx, rx) = channel();
+        tx.send(WsMessage::Text(String::from("one"))).unwrap();
+        tx.send(WsMessage::Text(String::from("two"))).unwrap();
+        tx.send(WsMessage::CloseGracefully(Duration::from_secs(5))).unwrap();
+        tx.send(WsMessage::Text(String::from("dropped"))).unwrap();
+        tx.send(WsMessage::Close).unwrap();
+
+        let mut sender = RecordingSender { frames: Vec::new() };
+        assert!(send_messages(&mut sender, &rx).is_ok());
+        let opcodes: Vec<u8> = sender.frames.iter().map(|f| f.0).collect();
+        assert_eq!(opcodes, vec![TEXT, TEXT, CLOSE]);
+        assert_eq!(sender.frames[0].1, b"one".to_vec());
+        assert_eq!(sender.frames[1].1, b"two".to_vec());
+    }
+
+    #[test]
+    fn graceful_close_send_times_out() {
+        let (tx, rx) = channel();
+        tx.send(WsMessage::CloseGracefully(Duration::from_millis(10))).unwrap();
+
+        // tx stays open like the receive loop's while slack doesn't close the connection
+        let mut sender = RecordingSender { frames: Vec::new() };
+        match send_messages(&mut sender, &rx) {
+            Err(Error::Timeout) => {}
+            _ => panic!("Graceful close without a close from slack did not time out."),
+        }
+        assert_eq!(sender.frames.len(), 1);
+        assert_eq!(sender.frames[0].0, CLOSE);
+    }
+
+    #[test]
+    fn local_shutdown_is_not_an_error() {
+        assert!(close_result(Some(1001), String::from("going away"), true).is_ok());
+        assert!(close_result(Some(1006), String::new(), true).is_ok());
+
+        let (tx, rx) = channel();
+        let (child, errors, ended) = spawn_recording_sender(rx);
+        let mut cli = RtmClient::new("token");
+        cli.set_outs(tx.clone());
+        cli.channel().unwrap().shutdown().unwrap();
+        // the connection is gone once the sender thread shut it down
+        let mut incoming = ScriptedIncoming {
+            messages: VecDeque::new(),
+            shut_down: false,
+        };
+        let mut handler = CountingHandler { errors: 0, closes: 0 };
+        assert!(cli.run_receiver(&mut handler, &mut incoming, &tx, child, &errors).is_ok());
+        assert!(ended.load(Ordering::SeqCst));
+
+        // a new connection isn't closing
+        cli.set_outs(tx);
+        assert!(!cli.closed_locally());
+    }
+
+    #[test]
+    fn join_sender_returns_sender_error() {
+        let (errors_tx, errors) = channel();
+        let child = thread::spawn(move || {
+            let _ = errors_tx.send(Error::Timeout);
+        });
+        match join_sender(child, &errors) {
+            Some(Error::Timeout) => {}
+            _ => panic!("Sender error was not returned."),
+        }
+
+        let (_errors_tx, errors) = channel();
+        assert!(join_sender(thread::spawn(|| {}), &errors).is_none());
+    }
+
+    #[test]
+    fn clones_share_caches() {
+        fn assert_shareable<T: Clone + Send + Sync>() {}
+        assert_shareable::<RtmClient>();
+
+        let cli = RtmClient::new("token");
+        let clone = cli.clone();
+        clone.state_mut().channel_ids.insert(String::from("general"), String::from("C123"));
+        assert_eq!(cli.get_channel_id("general"), Some(String::from("C123")));
+        assert_eq!(cli.evaluate_channel_id("#general").unwrap(), "C123");
+        assert!(cli.evaluate_channel_id("#random").is_err());
+    }
+}
@@ -0,0 +1,188 @@
+//
+// Copyright 2014-2016 the slack-rs authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Multi-party direct messages, see https://api.slack.com/types/mpim
+
+use hyper;
+
+use conversations::HistoryResponse;
+use error::Error;
+use web;
+use RtmClient;
+
+/// A multi-party direct message channel.
+/// Mpim ids start with a `G` like private groups, so they can only be told
+/// apart from groups by looking them up, see `RtmClient::channel_kind`.
+#[derive(Clone, Debug, RustcDecodable)]
+pub struct Mpim {
+    pub id: String,
+    pub name: String,
+    pub is_mpim: Option<bool>,
+    pub created: Option<i64>,
+    pub creator: Option<String>,
+    pub members: Option<Vec<String>>,
+    pub is_open: Option<bool>,
+}
+
+#[derive(RustcDecodable)]
+struct ListResponse {
+    groups: Vec<Mpim>,
+}
+
+#[derive(RustcDecodable)]
+struct OpenResponse {
+    group: Mpim,
+}
+
+impl RtmClient {
+    /// Returns a vector of the Mpims the bot/client is a member of, including closed ones,
+    /// see `Mpim::is_open`. Kept up to date with the `mpim_joined`, `mpim_open` and
+    /// `mpim_close` events.
+    /// Only valid after login.
+    pub fn get_mpims(&self) -> Vec<Mpim> {
+        self.state().mpims.clone()
+    }
+
+    /// Returns whether channel_id is a known mpim.
+    /// Only valid after login.
+    pub fn is_mpim(&self, channel_id: &str) -> bool {
+        self.state().mpims.iter().any(|m| m.id == channel_id)
+    }
+
+    /// Uses https://api.slack.com/methods/mpim.list to get a list of mpims
+    pub fn list_mpims(&self) -> Result<Vec<Mpim>, Error> {
+        let client = hyper::Client::new();
+        let data: ListResponse = try!(web::call(&client, "mpim.list", &[("token", &self.token[..])]));
+
+        Ok(data.groups)
+    }
+
+    /// Uses https://api.slack.com/methods/mpim.list to update mpims
+    pub fn update_mpims(&self) -> Result<Vec<Mpim>, Error> {
+        let mpims = try!(self.list_mpims());
+        self.state_mut().mpims = mpims.clone();
+        Ok(mpims)
+    }
+
+    /// Wraps https://api.slack.com/methods/mpim.open to open a multi-party direct message
+    /// channel with the given users, which is added to the mpims of the client.
+    pub fn mpim_open(&self, user_ids: &[&str]) -> Result<Mpim, Error> {
+        let users = user_ids.join(",");
+        let client = hyper::Client::new();
+        let res: OpenResponse = try!(web::call(&client,
+                                               "mpim.open",
+                                               &[("token", &self.token[..]), ("users", &users[..])]));
+        let mut state = self.state_mut();
+        if !state.mpims.iter().any(|m| m.id == res.group.id) {
+            state.mpims.push(res.group.clone());
+        }
+        Ok(res.group)
+    }
+
+    /// Wraps https://api.slack.com/methods/mpim.close to close a multi-party direct message
+    /// channel, which is marked as closed in the mpims of the client.
+    pub fn mpim_close(&self, channel_id: &str) -> Result<(), Error> {
+        let client = hyper::Client::new();
+        try!(web::call_json(&client,
+                            "mpim.close",
+                            &[("token", &self.token[..]), ("channel", channel_id)]));
+        self.set_mpim_open(channel_id, false);
+        Ok(())
+    }
+
+    /// Wraps https://api.slack.com/methods/mpim.history to retrieve the history of messages and
+    /// events from a multi-party direct message channel.
+    pub fn mpim_history(&self,
+                        channel_id: &str,
+                        latest: Option<&str>,
+                        oldest: Option<&str>,
+                        inclusive: Option<bool>,
+                        count: Option<u32>)
+                        -> Result<HistoryResponse, Error> {
+        let count = count.map(|c| c.to_string());
+        let mut params = vec![("token", &self.token[..]), ("channel", channel_id)];
+        web::push_opt(&mut params, "latest", latest);
+        web::push_opt(&mut params, "oldest", oldest);
+        web::push_opt(&mut params, "inclusive", inclusive.map(|i| if i { "1" } else { "0" }));
+        web::push_opt(&mut params, "count", count.as_ref().map(|c| &c[..]));
+
+        let client = hyper::Client::new();
+        web::call(&client, "mpim.history", &params)
+    }
+
+    /// Wraps https://api.slack.com/methods/mpim.mark to move the read cursor in a multi-party
+    /// direct message channel.
+    pub fn mpim_mark(&self, channel_id: &str, timestamp: &str) -> Result<(), Error> {
+        let client = hyper::Client::new();
+        try!(web::call_json(&client,
+                            "mpim.mark",
+                            &[("token", &self.token[..]), ("channel", channel_id), ("ts", timestamp)]));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustc_serialize::json;
+    use events::Event;
+    use ChannelKind;
+
+    fn event(raw_json: &str) -> Event {
+        json::decode(raw_json).unwrap()
+    }
+
+    #[test]
+    fn mpims_follow_events() {
+        let cli = RtmClient::new("token");
+        assert!(!cli.is_mpim("G123"));
+        cli.update_caches(&event(r#"{"type": "mpim_joined", "channel": {"id": "G123",
+                                     "name": "mpdm-a--b--c-1", "is_mpim": true, "is_open": true}}"#));
+        assert!(cli.is_mpim("G123"));
+        assert_eq!(cli.channel_kind("G123").unwrap(), ChannelKind::Mpim);
+        assert_eq!(cli.channel_kind("G456").unwrap(), ChannelKind::Group);
+
+        cli.update_caches(&event(r#"{"type": "mpim_close", "user": "U1", "channel": "G123"}"#));
+        assert!(cli.is_mpim("G123"));
+        assert_eq!(cli.get_mpims()[0].is_open, Some(false));
+        cli.update_caches(&event(r#"{"type": "mpim_open", "user": "U1", "channel": "G123"}"#));
+        assert_eq!(cli.get_mpims()[0].is_open, Some(true));
+        assert_eq!(cli.get_mpims().len(), 1);
+    }
+
+    #[test]
+    fn mpim_open_adds_unknown_mpims() {
+        let cli = RtmClient::new("token");
+        cli.update_caches(&event(r#"{"type": "mpim_open", "user": "U1", "channel": "G789"}"#));
+        assert!(cli.is_mpim("G789"));
+        assert_eq!(cli.channel_kind("G789").unwrap(), ChannelKind::Mpim);
+    }
+
+    #[test]
+    fn message_channels_are_told_apart() {
+        let cli = RtmClient::new("token");
+        cli.update_caches(&event(r#"{"type": "mpim_open", "user": "U1", "channel": "G789"}"#));
+        let message = event(r#"{"type": "message", "channel": "G789", "user": "U1",
+                                "text": "hi", "ts": "1355517523.000005"}"#);
+        match message {
+            Event::Message(::Message::Standard { channel: Some(ref channel), .. }) => {
+                assert!(cli.is_mpim(channel));
+                assert_eq!(cli.channel_kind(channel).unwrap(), ChannelKind::Mpim);
+            }
+            _ => panic!("Event was not a standard message with a channel."),
+        }
+    }
+}
@@ -0,0 +1,268 @@
+//
+// Copyright 2014-2016 the slack-rs authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Iterators over Web API methods that return their results in pages.
+//!
+//! ```no_run
+//! # let cli = slack::RtmClient::new("token");
+//! for message in cli.channels_history_iter("C12345678", None, Some(1000)) {
+//!     println!("{:?}", message);
+//! }
+//! ```
+
+use std::collections::VecDeque;
+
+use hyper;
+use rustc_serialize::Decodable;
+
+use api::{Channel, Message, User};
+use error::Error;
+use web;
+use RtmClient;
+
+/// Number of items requested per page.
+const PAGE_SIZE: usize = 200;
+
+/// A single page of results.
+pub struct Page<T> {
+    /// The items in this page.
+    pub items: Vec<T>,
+    /// The cursor to pass when fetching the next page, None on the last page.
+    pub next: Option<String>,
+}
+
+/// An iterator that lazily fetches pages as it is consumed.
+///
+/// Iteration stops after the last page, once the optional limit is reached, or
+/// after yielding the first error.
+pub struct Paginated<T> {
+    fetch: Box<FnMut(Option<&str>) -> Result<Page<T>, Error>>,
+    cursor: Option<String>,
+    buffer: VecDeque<T>,
+    remaining: Option<usize>,
+    done: bool,
+}
+
+impl<T> Paginated<T> {
+    /// Creates an iterator from fetch, which is called with the cursor returned by
+    /// the previous page (None for the first page), yielding at most limit items.
+    pub fn new<F>(fetch: F, limit: Option<usize>) -> Paginated<T>
+        where F: FnMut(Option<&str>) -> Result<Page<T>, Error> + 'static
+    {
+        Paginated {
+            fetch: Box::new(fetch),
+            cursor: None,
+            buffer: VecDeque::new(),
+            remaining: limit,
+            done: false,
+        }
+    }
+}
+
+impl<T> Iterator for Paginated<T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Result<T, Error>> {
+        if self.remaining == Some(0) {
+            return None;
+        }
+        while self.buffer.is_empty() {
+            if self.done {
+                return None;
+            }
+            match (self.fetch)(self.cursor.as_ref().map(|c| &c[..])) {
+                Ok(page) => {
+                    self.buffer.extend(page.items);
+                    self.done = page.next.is_none();
+                    self.cursor = page.next;
+                }
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+        if let Some(ref mut remaining) = self.remaining {
+            *remaining -= 1;
+        }
+        self.buffer.pop_front().map(Ok)
+    }
+}
+
+/// Fetches pages of a history method, walking backwards in time using `latest`.
+fn history(method: &'static str,
+           token: String,
+           channel: String,
+           oldest: Option<String>,
+           limit: Option<usize>)
+           -> Paginated<Message> {
+    let client = hyper::Client::new();
+    let count = PAGE_SIZE.to_string();
+    Paginated::new(move |latest| {
+        let mut params = vec![("token", &token[..]), ("channel", &channel[..]), ("count", &count[..])];
+        web::push_opt(&mut params, "latest", latest);
+        web::push_opt(&mut params, "oldest", oldest.as_ref().map(|o| &o[..]));
+        let json = try!(web::call_json(&client, method, &params));
+
+        let messages: Vec<Message> = try!(web::decode_items(&json, "messages"));
+        let has_more = json.find("has_more").and_then(|m| m.as_boolean()).unwrap_or(false);
+        // messages are returned newest first, so the next page ends at the last one
+        let last_ts = json.find("messages")
+                          .and_then(|m| m.as_array())
+                          .and_then(|m| m.last())
+                          .and_then(|m| m.find("ts"))
+                          .and_then(|ts| ts.as_string())
+                          .map(|ts| ts.to_string());
+        Ok(Page {
+            items: messages,
+            next: if has_more { last_ts } else { None },
+        })
+    },
+                   limit)
+}
+
+/// Fetches pages of a list method that uses cursor based pagination.
+/// extra are the params besides the token, page size and cursor.
+fn cursor_list<T: Decodable>(method: &'static str,
+                             field: &'static str,
+                             token: String,
+                             extra: Vec<(&'static str, String)>,
+                             limit: Option<usize>)
+                             -> Paginated<T> {
+    let client = hyper::Client::new();
+    let page_size = PAGE_SIZE.to_string();
+    Paginated::new(move |cursor| {
+        let mut params = vec![("token", &token[..]), ("limit", &page_size[..])];
+        params.extend(extra.iter().map(|&(name, ref value)| (name, &value[..])));
+        web::push_opt(&mut params, "cursor", cursor);
+        let json = try!(web::call_json(&client, method, &params));
+        Ok(Page {
+            items: try!(web::decode_items(&json, field)),
+            next: web::next_cursor(&json),
+        })
+    },
+                   limit)
+}
+
+impl RtmClient {
+    /// Iterates over the messages of a channel, newest first, fetching further pages of
+    /// https://api.slack.com/methods/channels.history as needed.
+    /// Stops at oldest if given, and after limit messages if given.
+    pub fn channels_history_iter(&self, channel_id: &str, oldest: Option<&str>, limit: Option<usize>) -> Paginated<Message> {
+        history("channels.history",
+                self.token.clone(),
+                channel_id.to_string(),
+                oldest.map(|o| o.to_string()),
+                limit)
+    }
+
+    /// Iterates over the messages of a direct message channel, newest first, fetching
+    /// further pages of https://api.slack.com/methods/im.history as needed.
+    /// Stops at oldest if given, and after limit messages if given.
+    pub fn im_history_iter(&self, channel_id: &str, oldest: Option<&str>, limit: Option<usize>) -> Paginated<Message> {
+        history("im.history",
+                self.token.clone(),
+                channel_id.to_string(),
+                oldest.map(|o| o.to_string()),
+                limit)
+    }
+
+    /// Iterates over the messages of a multi-party direct message channel, newest first,
+    /// fetching further pages of https://api.slack.com/methods/mpim.history as needed.
+    /// Stops at oldest if given, and after limit messages if given.
+    pub fn mpim_history_iter(&self, channel_id: &str, oldest: Option<&str>, limit: Option<usize>) -> Paginated<Message> {
+        history("mpim.history",
+                self.token.clone(),
+                channel_id.to_string(),
+                oldest.map(|o| o.to_string()),
+                limit)
+    }
+
+    /// Iterates over the messages of a conversation, newest first, fetching further pages of
+    /// https://api.slack.com/methods/conversations.history as needed. channel can be an id
+    /// or a `#name`, see `conversations`.
+    /// Stops at oldest if given, and after limit messages if given.
+    pub fn conversations_history_iter(&self,
+                                      channel: &str,
+                                      oldest: Option<&str>,
+                                      limit: Option<usize>)
+                                      -> Result<Paginated<Message>, Error> {
+        let mut extra = vec![("channel", try!(self.evaluate_channel_id(channel)))];
+        if let Some(oldest) = oldest {
+            extra.push(("oldest", oldest.to_string()));
+        }
+        Ok(cursor_list("conversations.history", "messages", self.token.clone(), extra, limit))
+    }
+
+    /// Iterates over all users of the team, fetching further pages of
+    /// https://api.slack.com/methods/users.list as needed.
+    pub fn list_users_iter(&self, limit: Option<usize>) -> Paginated<User> {
+        cursor_list("users.list", "members", self.token.clone(), Vec::new(), limit)
+    }
+
+    /// Iterates over all channels of the team, fetching further pages of
+    /// https://api.slack.com/methods/channels.list as needed.
+    pub fn list_channels_iter(&self, limit: Option<usize>) -> Paginated<Channel> {
+        cursor_list("channels.list", "channels", self.token.clone(), Vec::new(), limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use error::Error;
+
+    fn numbers(pages: usize) -> Paginated<usize> {
+        Paginated::new(move |cursor| {
+            let page: usize = cursor.map(|c| c.parse().unwrap()).unwrap_or(0);
+            Ok(Page {
+                items: vec![page * 2, page * 2 + 1],
+                next: if page + 1 < pages { Some((page + 1).to_string()) } else { None },
+            })
+        },
+                       None)
+    }
+
+    #[test]
+    fn follows_cursors() {
+        let items: Vec<usize> = numbers(3).map(|n| n.unwrap()).collect();
+        assert_eq!(items, vec![0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn stops_at_limit() {
+        let mut fetched = 0;
+        let items: Vec<usize> = Paginated::new(move |_| {
+                                                   fetched += 1;
+                                                   assert!(fetched == 1);
+                                                   Ok(Page {
+                                                       items: vec![1, 2, 3],
+                                                       next: Some(String::from("more")),
+                                                   })
+                                               },
+                                               Some(2))
+                                    .map(|n| n.unwrap())
+                                    .collect();
+        assert_eq!(items, vec![1, 2]);
+    }
+
+    #[test]
+    fn stops_after_error() {
+        let mut iter: Paginated<usize> = Paginated::new(|_| Err(Error::Internal(String::from("boom"))), None);
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+    }
+}
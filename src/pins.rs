@@ -0,0 +1,139 @@
+//
+// Copyright 2014-2016 the slack-rs authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Pinning and unpinning items in a channel.
+
+use hyper;
+
+use error::Error;
+use events::{self, Item};
+use web;
+use RtmClient;
+
+impl RtmClient {
+    /// Returns the channel param of a pins method for the item in channel, which is
+    /// looked up with get_channel_id if it starts with a #. Messages carry their channel,
+    /// which channel has to match.
+    fn pins_channel(&self, channel: &str, item: &Item) -> Result<Option<String>, Error> {
+        let channel = try!(self.evaluate_channel_id(channel));
+        match *item {
+            Item::Message { channel: ref message_channel, .. } => {
+                if *message_channel != channel {
+                    return Err(Error::Internal(format!("a message of {} can't be pinned to {}",
+                                                       message_channel,
+                                                       channel)));
+                }
+                // the channel is one of the params of the message
+                Ok(None)
+            }
+            Item::File { .. } | Item::FileComment { .. } => Ok(Some(channel)),
+            Item::Channel { .. } | Item::Im { .. } | Item::Group { .. } => {
+                Err(Error::Internal(String::from("only messages, files and file comments can be pinned")))
+            }
+        }
+    }
+
+    /// Calls a pins method for the item in channel.
+    fn call_pins(&self, method: &str, channel: &str, item: &Item) -> Result<(), Error> {
+        let channel = try!(self.pins_channel(channel, item));
+        let mut params = vec![("token", &self.token[..])];
+        web::push_opt(&mut params, "channel", channel.as_ref().map(|c| &c[..]));
+        params.extend(item.api_params());
+
+        let client = hyper::Client::new();
+        try!(web::call_json(&client, method, &params));
+        Ok(())
+    }
+
+    /// Wraps https://api.slack.com/methods/pins.add to pin an item to a channel,
+    /// which will be looked up with get_channel_id if it starts with a #.
+    /// A message can only be pinned to its own channel.
+    pub fn add_pin(&self, channel: &str, item: &Item) -> Result<(), Error> {
+        self.call_pins("pins.add", channel, item)
+    }
+
+    /// Wraps https://api.slack.com/methods/pins.remove to un-pin an item from a channel.
+    /// The channel is used as in `add_pin`.
+    pub fn remove_pin(&self, channel: &str, item: &Item) -> Result<(), Error> {
+        self.call_pins("pins.remove", channel, item)
+    }
+
+    /// Wraps https://api.slack.com/methods/pins.list to list the items pinned to a channel.
+    /// If channel starts with a # then it will be looked up with get_channel_id.
+    /// Items of types that `Item` doesn't know are left out.
+    pub fn list_pins(&self, channel: &str) -> Result<Vec<Item>, Error> {
+        let channel = try!(self.evaluate_channel_id(channel));
+        let client = hyper::Client::new();
+        let json = try!(web::call_json(&client,
+                                       "pins.list",
+                                       &[("token", &self.token[..]), ("channel", &channel[..])]));
+        web::decode_typed_items(&json, "items", events::ITEM_TYPES)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustc_serialize::json::Json;
+
+    #[test]
+    fn decode_pinned_items() {
+        let json = Json::from_str(r#"{
+            "ok": true,
+            "items": [
+                {
+                    "type": "message",
+                    "channel": "C123",
+                    "message": {"type": "message", "text": "hi", "ts": "1360782400.498405"}
+                },
+                {"type": "file", "channel": "C123", "file": {"id": "F123", "name": "notes.txt"}},
+                {
+                    "type": "file_comment",
+                    "channel": "C123",
+                    "file": {"id": "F123"},
+                    "comment": {"id": "Fc123", "comment": "nice"}
+                },
+                {"type": "call", "channel": "C123", "call": {"id": "R123"}}
+            ]
+        }"#).unwrap();
+        let items: Vec<Item> = web::decode_typed_items(&json, "items", events::ITEM_TYPES).unwrap();
+        assert_eq!(items,
+                   vec![Item::Message {
+                            channel: String::from("C123"),
+                            ts: String::from("1360782400.498405"),
+                        },
+                        Item::File { file: String::from("F123") },
+                        Item::FileComment {
+                            file: Some(String::from("F123")),
+                            comment: String::from("Fc123"),
+                        }]);
+    }
+
+    #[test]
+    fn message_pins_use_their_own_channel() {
+        let cli = RtmClient::new("token");
+        let message = Item::Message {
+            channel: String::from("C123"),
+            ts: String::from("1360782400.498405"),
+        };
+        assert_eq!(cli.pins_channel("C123", &message).unwrap(), None);
+        assert!(cli.pins_channel("C456", &message).is_err());
+
+        let file = Item::File { file: String::from("F123") };
+        assert_eq!(cli.pins_channel("C456", &file).unwrap(), Some(String::from("C456")));
+        assert!(cli.pins_channel("C123", &Item::Channel { channel: String::from("C123") }).is_err());
+    }
+}
@@ -0,0 +1,354 @@
+//
+// Copyright 2014-2016 the slack-rs authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Adding, removing and listing emoji reactions.
+
+use hyper;
+use rustc_serialize::json::Json;
+
+use error::Error;
+use events::Item;
+use files::Paging;
+use web;
+use RtmClient;
+
+/// Something that can be reacted to: a message, a file or a file comment.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReactionTarget {
+    /// A message, identified by its channel (an id or a `#name`) and timestamp.
+    Message {
+        channel: String,
+        timestamp: String,
+    },
+    /// A file id.
+    File(String),
+    /// A file comment id.
+    FileComment(String),
+}
+
+impl ReactionTarget {
+    /// Returns the target an item refers to, or None for items that can't be reacted to.
+    pub fn from_item(item: &Item) -> Option<ReactionTarget> {
+        match *item {
+            Item::Message { ref channel, ref ts } => {
+                Some(ReactionTarget::Message {
+                    channel: channel.clone(),
+                    timestamp: ts.clone(),
+                })
+            }
+            Item::File { ref file } => Some(ReactionTarget::File(file.clone())),
+            Item::FileComment { ref comment, .. } => Some(ReactionTarget::FileComment(comment.clone())),
+            Item::Channel { .. } | Item::Im { .. } | Item::Group { .. } => None,
+        }
+    }
+}
+
+impl From<ReactionTarget> for Item {
+    fn from(target: ReactionTarget) -> Item {
+        match target {
+            ReactionTarget::Message { channel, timestamp } => {
+                Item::Message {
+                    channel: channel,
+                    ts: timestamp,
+                }
+            }
+            ReactionTarget::File(file) => Item::File { file: file },
+            ReactionTarget::FileComment(comment) => {
+                Item::FileComment {
+                    file: None,
+                    comment: comment,
+                }
+            }
+        }
+    }
+}
+
+/// A reaction and the users that added it.
+#[derive(Clone, Debug, RustcDecodable)]
+pub struct Reaction {
+    pub name: String,
+    pub count: u32,
+    pub users: Vec<String>,
+}
+
+/// An item and its reactions, as returned by `RtmClient::list_reactions`.
+#[derive(Clone, Debug)]
+pub struct ReactedItem {
+    pub target: ReactionTarget,
+    pub reactions: Vec<Reaction>,
+}
+
+/// Response of https://api.slack.com/methods/reactions.list
+#[derive(Clone, Debug)]
+pub struct ListResponse {
+    pub items: Vec<ReactedItem>,
+    pub paging: Option<Paging>,
+}
+
+/// Reads the target and reactions of an item in a reactions.get or reactions.list response,
+/// where the reactions are on the message, file or comment object.
+fn reacted_item(json: &Json) -> Result<ReactedItem, Error> {
+    let ty = json.find("type").and_then(|t| t.as_string()).unwrap_or("");
+    let (target, object) = match ty {
+        "message" => {
+            let channel = json.find("channel").and_then(|c| c.as_string());
+            let message = json.find("message");
+            let ts = message.and_then(|m| m.find("ts")).and_then(|ts| ts.as_string());
+            match (channel, ts) {
+                (Some(channel), Some(ts)) => {
+                    (ReactionTarget::Message {
+                        channel: channel.to_string(),
+                        timestamp: ts.to_string(),
+                    },
+                     message)
+                }
+                _ => return Err(Error::Internal(String::from("reacted message without channel or ts"))),
+            }
+        }
+        "file" | "file_comment" => {
+            let key = if ty == "file" { "file" } else { "comment" };
+            let object = json.find(key);
+            match object.and_then(|o| o.find("id")).and_then(|id| id.as_string()) {
+                Some(id) if ty == "file" => (ReactionTarget::File(id.to_string()), object),
+                Some(id) => (ReactionTarget::FileComment(id.to_string()), object),
+                None => return Err(Error::Internal(format!("reacted {} without id", ty))),
+            }
+        }
+        _ => return Err(Error::Internal(format!("unknown reacted item type: {}", ty))),
+    };
+    let reactions = match object {
+        Some(object) => try!(web::decode_items(object, "reactions")),
+        None => Vec::new(),
+    };
+    Ok(ReactedItem {
+        target: target,
+        reactions: reactions,
+    })
+}
+
+impl RtmClient {
+    /// Resolves the channel of a message target and returns the params addressing it.
+    fn reaction_params(&self, target: &ReactionTarget) -> Result<Vec<(&'static str, String)>, Error> {
+        Ok(match *target {
+            ReactionTarget::Message { ref channel, ref timestamp } => {
+                vec![("channel", try!(self.evaluate_channel_id(channel))), ("timestamp", timestamp.clone())]
+            }
+            ReactionTarget::File(ref file) => vec![("file", file.clone())],
+            ReactionTarget::FileComment(ref comment) => vec![("file_comment", comment.clone())],
+        })
+    }
+
+    /// Calls a reactions method for target with the extra params.
+    fn call_reactions(&self, method: &str, target: &ReactionTarget, extra: &[(&str, &str)]) -> Result<Json, Error> {
+        let target_params = try!(self.reaction_params(target));
+        let mut params = vec![("token", &self.token[..])];
+        params.extend(target_params.iter().map(|&(k, ref v)| (k, &v[..])));
+        params.extend_from_slice(extra);
+
+        let client = hyper::Client::new();
+        web::call_json(&client, method, &params)
+    }
+
+    /// Wraps https://api.slack.com/methods/reactions.add to add an emoji reaction to a
+    /// message, file or file comment.
+    pub fn add_reaction(&self, emoji_name: &str, target: &ReactionTarget) -> Result<(), Error> {
+        try!(self.call_reactions("reactions.add", target, &[("name", emoji_name)]));
+        Ok(())
+    }
+
+    /// Wraps https://api.slack.com/methods/reactions.remove to remove an emoji reaction from a
+    /// message, file or file comment.
+    pub fn remove_reaction(&self, emoji_name: &str, target: &ReactionTarget) -> Result<(), Error> {
+        try!(self.call_reactions("reactions.remove", target, &[("name", emoji_name)]));
+        Ok(())
+    }
+
+    /// Wraps https://api.slack.com/methods/reactions.remove to remove an emoji reaction from a
+    /// message. If channel starts with a # then it will be looked up with get_channel_id
+    pub fn remove_reaction_timestamp(&self, emoji_name: &str, channel: &str, timestamp: &str) -> Result<(), Error> {
+        self.remove_reaction(emoji_name,
+                             &ReactionTarget::Message {
+                                 channel: channel.to_string(),
+                                 timestamp: timestamp.to_string(),
+                             })
+    }
+
+    /// Wraps https://api.slack.com/methods/reactions.remove to remove an emoji reaction from a file
+    pub fn remove_reaction_file(&self, emoji_name: &str, file: &str) -> Result<(), Error> {
+        self.remove_reaction(emoji_name, &ReactionTarget::File(file.to_string()))
+    }
+
+    /// Wraps https://api.slack.com/methods/reactions.remove to remove an emoji reaction from a
+    /// file comment
+    pub fn remove_reaction_file_comment(&self, emoji_name: &str, file_comment: &str) -> Result<(), Error> {
+        self.remove_reaction(emoji_name, &ReactionTarget::FileComment(file_comment.to_string()))
+    }
+
+    /// Wraps https://api.slack.com/methods/reactions.get to get the reactions on a message,
+    /// file or file comment. If full is false, the user lists may be truncated.
+    pub fn get_reactions(&self, target: &ReactionTarget, full: bool) -> Result<Vec<Reaction>, Error> {
+        let full = if full { "true" } else { "false" };
+        let json = try!(self.call_reactions("reactions.get", target, &[("full", full)]));
+        let item = try!(reacted_item(&json));
+        Ok(item.reactions)
+    }
+
+    /// Wraps https://api.slack.com/methods/reactions.list to list the items reacted to by a
+    /// user, or by the connected user if user_id is None.
+    pub fn list_reactions(&self,
+                          user_id: Option<&str>,
+                          full: bool,
+                          count: Option<u32>,
+                          page: Option<u32>)
+                          -> Result<ListResponse, Error> {
+        let count = count.map(|c| c.to_string());
+        let page = page.map(|p| p.to_string());
+        let mut params = vec![("token", &self.token[..]), ("full", if full { "true" } else { "false" })];
+        web::push_opt(&mut params, "user", user_id);
+        web::push_opt(&mut params, "count", count.as_ref().map(|c| &c[..]));
+        web::push_opt(&mut params, "page", page.as_ref().map(|p| &p[..]));
+
+        let client = hyper::Client::new();
+        let json = try!(web::call_json(&client, "reactions.list", &params));
+        list_response(&json)
+    }
+}
+
+/// Reads a reactions.list response.
+fn list_response(json: &Json) -> Result<ListResponse, Error> {
+    let mut items = Vec::new();
+    if let Some(array) = json.find("items").and_then(|i| i.as_array()) {
+        for item in array.iter() {
+            items.push(try!(reacted_item(item)));
+        }
+    }
+    let paging = match json.find("paging") {
+        Some(paging) => Some(try!(web::decode(paging.clone()))),
+        None => None,
+    };
+    Ok(ListResponse {
+        items: items,
+        paging: paging,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use events::Item;
+    use rustc_serialize::json::Json;
+
+    fn targets() -> Vec<(Item, ReactionTarget)> {
+        vec![(Item::Message {
+                  channel: String::from("C123"),
+                  ts: String::from("1360782400.498405"),
+              },
+              ReactionTarget::Message {
+                  channel: String::from("C123"),
+                  timestamp: String::from("1360782400.498405"),
+              }),
+             (Item::File { file: String::from("F123") }, ReactionTarget::File(String::from("F123"))),
+             (Item::FileComment {
+                  file: None,
+                  comment: String::from("Fc123"),
+              },
+              ReactionTarget::FileComment(String::from("Fc123")))]
+    }
+
+    #[test]
+    fn targets_from_items() {
+        for (item, target) in targets() {
+            assert_eq!(ReactionTarget::from_item(&item), Some(target.clone()));
+            assert_eq!(Item::from(target), item);
+        }
+        let commented = Item::FileComment {
+            file: Some(String::from("F123")),
+            comment: String::from("Fc123"),
+        };
+        assert_eq!(ReactionTarget::from_item(&commented),
+                   Some(ReactionTarget::FileComment(String::from("Fc123"))));
+        assert_eq!(ReactionTarget::from_item(&Item::Channel { channel: String::from("C123") }),
+                   None);
+    }
+
+    #[test]
+    fn parse_reacted_items() {
+        let reaction = r#""reactions": [{"name": "thumbsup", "count": 2, "users": ["U1", "U2"]}]"#;
+        let cases = vec![(format!(r#"{{"type": "message", "channel": "C123",
+                                      "message": {{"ts": "1360782400.498405", {}}}}}"#,
+                                  reaction),
+                          ReactionTarget::Message {
+                              channel: String::from("C123"),
+                              timestamp: String::from("1360782400.498405"),
+                          }),
+                         (format!(r#"{{"type": "file", "file": {{"id": "F123", {}}}}}"#, reaction),
+                          ReactionTarget::File(String::from("F123"))),
+                         (format!(r#"{{"type": "file_comment", "file": {{"id": "F123"}},
+                                      "comment": {{"id": "Fc123", {}}}}}"#,
+                                  reaction),
+                          ReactionTarget::FileComment(String::from("Fc123")))];
+        for (json, target) in cases {
+            let item = reacted_item(&Json::from_str(&json).unwrap()).unwrap();
+            assert_eq!(item.target, target);
+            assert_eq!(item.reactions.len(), 1);
+            assert_eq!(item.reactions[0].name, "thumbsup");
+            assert_eq!(item.reactions[0].users, vec!["U1", "U2"]);
+        }
+        assert!(reacted_item(&Json::from_str(r#"{"type": "channel"}"#).unwrap()).is_err());
+        assert!(reacted_item(&Json::from_str(r#"{"type": "message", "channel": "C1"}"#).unwrap()).is_err());
+    }
+
+    #[test]
+    fn parse_list_response() {
+        let json = Json::from_str(r#"{
+            "ok": true,
+            "items": [
+                {
+                    "type": "message",
+                    "channel": "C3UKJTQAC",
+                    "message": {
+                        "type": "message",
+                        "text": "Hello",
+                        "user": "U2U85N1RZ",
+                        "ts": "1507849573.000090",
+                        "reactions": [{"name": "grinning", "users": ["U2U85N1RZ"], "count": 1}]
+                    }
+                },
+                {
+                    "type": "file",
+                    "file": {
+                        "id": "F7H0D7ZA4",
+                        "name": "computer.gif",
+                        "reactions": [{"name": "stuck_out_tongue_winking_eye", "users": ["U2U85N1RZ"], "count": 1}]
+                    }
+                }
+            ],
+            "paging": {"count": 100, "total": 2, "page": 1, "pages": 1}
+        }"#)
+                       .unwrap();
+        let res = list_response(&json).unwrap();
+        assert_eq!(res.items.len(), 2);
+        assert_eq!(res.items[0].target,
+                   ReactionTarget::Message {
+                       channel: String::from("C3UKJTQAC"),
+                       timestamp: String::from("1507849573.000090"),
+                   });
+        assert_eq!(res.items[0].reactions[0].name, "grinning");
+        assert_eq!(res.items[1].target, ReactionTarget::File(String::from("F7H0D7ZA4")));
+        assert_eq!(res.items[1].reactions[0].count, 1);
+        assert_eq!(res.paging.unwrap().total, 2);
+    }
+}
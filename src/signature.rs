@@ -0,0 +1,112 @@
+//
+// Copyright 2014-2016 the slack-rs authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Verifying that requests were sent by slack, see
+//! https://api.slack.com/authentication/verifying-requests-from-slack
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crypto::hmac::Hmac;
+use crypto::mac::Mac;
+use crypto::sha2::Sha256;
+use crypto::util::fixed_time_eq;
+use rustc_serialize::hex::ToHex;
+
+use error::Error;
+
+/// Name of the header holding the request signature.
+pub const SIGNATURE_HEADER: &'static str = "X-Slack-Signature";
+
+/// Name of the header holding the request timestamp.
+pub const TIMESTAMP_HEADER: &'static str = "X-Slack-Request-Timestamp";
+
+/// Requests older (or newer) than this many seconds are rejected as possible replays.
+pub const MAX_REQUEST_AGE: i64 = 60 * 5;
+
+/// Returns the `v0=` signature of a request body sent at timestamp.
+pub fn sign(signing_secret: &str, timestamp: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::new(Sha256::new(), signing_secret.as_bytes());
+    mac.input(b"v0:");
+    mac.input(timestamp.as_bytes());
+    mac.input(b":");
+    mac.input(body);
+    format!("v0={}", mac.result().code().to_hex())
+}
+
+/// Checks the signature and timestamp headers of a request against its raw body,
+/// with `now` as the current unix time.
+pub fn verify_at(signing_secret: &str, timestamp: &str, signature: &str, body: &[u8], now: i64) -> Result<(), Error> {
+    let sent = match timestamp.parse::<i64>() {
+        Ok(sent) => sent,
+        Err(_) => return Err(Error::Signature(format!("invalid request timestamp: {}", timestamp))),
+    };
+    if (now - sent).abs() > MAX_REQUEST_AGE {
+        return Err(Error::Signature(String::from("request timestamp is too old")));
+    }
+    let expected = sign(signing_secret, timestamp, body);
+    if fixed_time_eq(expected.as_bytes(), signature.as_bytes()) {
+        Ok(())
+    } else {
+        Err(Error::Signature(String::from("request signature does not match")))
+    }
+}
+
+/// Checks the signature and timestamp headers of a request against its raw body.
+pub fn verify(signing_secret: &str, timestamp: &str, signature: &str, body: &[u8]) -> Result<(), Error> {
+    let now = match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(now) => now.as_secs() as i64,
+        Err(_) => return Err(Error::Internal(String::from("system time is before the unix epoch"))),
+    };
+    verify_at(signing_secret, timestamp, signature, body, now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The example from https://api.slack.com/authentication/verifying-requests-from-slack
+    const SECRET: &'static str = "8f742231b10e8888abcd99yyyzzz85a5";
+    const TIMESTAMP: &'static str = "1531420618";
+    const BODY: &'static str = "token=xyzz0WbapA4vBCDEFasx0q6G&team_id=T1DC2JH3J&team_domain=testteamnow&\
+                                channel_id=G8PSS9T3V&channel_name=foobar&user_id=U2CERLKJA&\
+                                user_name=roadrunner&command=%2Fwebhook-collect&text=&\
+                                response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2FT1DC2JH3J%2F\
+                                397700885554%2F96rGlfmibIGlgcZRskXaIFfN&\
+                                trigger_id=398738663015.47445629121.803a0bc887a14d10d2c447fce8b6703c";
+    const SIGNATURE: &'static str = "v0=a2114d57b48eac39b9ad189dd8316235a7b4a8d21a10bd27519666489c69b503";
+
+    #[test]
+    fn sign_example() {
+        assert_eq!(sign(SECRET, TIMESTAMP, BODY.as_bytes()), SIGNATURE);
+    }
+
+    #[test]
+    fn verify_accepts_recent_requests() {
+        assert!(verify_at(SECRET, TIMESTAMP, SIGNATURE, BODY.as_bytes(), 1531420618 + 60).is_ok());
+    }
+
+    #[test]
+    fn verify_rejects_replays() {
+        assert!(verify_at(SECRET, TIMESTAMP, SIGNATURE, BODY.as_bytes(), 1531420618 + 301).is_err());
+    }
+
+    #[test]
+    fn verify_rejects_tampered_bodies() {
+        let body = BODY.replace("foobar", "foobaz");
+        assert!(verify_at(SECRET, TIMESTAMP, SIGNATURE, body.as_bytes(), 1531420618).is_err());
+        assert!(verify_at(SECRET, "junk", SIGNATURE, BODY.as_bytes(), 1531420618).is_err());
+    }
+}
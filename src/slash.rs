@@ -0,0 +1,265 @@
+//
+// Copyright 2014-2016 the slack-rs authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Receiving and replying to slash commands, see https://api.slack.com/interactivity/slash-commands
+//!
+//! Commands arrive either as a form encoded HTTP request, see `SlashCommand::from_request`,
+//! or as the payload of a `slash_commands` Socket Mode envelope, see
+//! `SlashCommand::from_envelope`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::str;
+
+use hyper;
+use rustc_serialize::json::Json;
+use url::form_urlencoded;
+
+use error::Error;
+use signature;
+use socket_mode::Envelope;
+use web;
+
+/// A slash command invocation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlashCommand {
+    pub team_id: String,
+    pub team_domain: Option<String>,
+    pub enterprise_id: Option<String>,
+    pub channel_id: String,
+    pub channel_name: Option<String>,
+    pub user_id: String,
+    pub user_name: Option<String>,
+    /// The command, including the leading `/`.
+    pub command: String,
+    /// Everything after the command.
+    pub text: String,
+    /// Url to reply to for 30 minutes after the command, see `SlashCommand::respond_later`.
+    pub response_url: String,
+    /// Id to open a modal with, valid for 3 seconds.
+    pub trigger_id: String,
+    pub api_app_id: Option<String>,
+}
+
+impl SlashCommand {
+    /// Builds a command from its fields, failing if a required field is missing.
+    fn from_fields(mut fields: HashMap<String, String>) -> Result<SlashCommand, Error> {
+        let team_id = try!(web::required_field(&mut fields, "team_id", "slash command"));
+        let channel_id = try!(web::required_field(&mut fields, "channel_id", "slash command"));
+        let user_id = try!(web::required_field(&mut fields, "user_id", "slash command"));
+        let command = try!(web::required_field(&mut fields, "command", "slash command"));
+        let response_url = try!(web::required_field(&mut fields, "response_url", "slash command"));
+        let trigger_id = try!(web::required_field(&mut fields, "trigger_id", "slash command"));
+        Ok(SlashCommand {
+            team_id: team_id,
+            team_domain: fields.remove("team_domain"),
+            enterprise_id: fields.remove("enterprise_id"),
+            channel_id: channel_id,
+            channel_name: fields.remove("channel_name"),
+            user_id: user_id,
+            user_name: fields.remove("user_name"),
+            command: command,
+            text: fields.remove("text").unwrap_or_default(),
+            response_url: response_url,
+            trigger_id: trigger_id,
+            api_app_id: fields.remove("api_app_id"),
+        })
+    }
+
+    /// Parses the `application/x-www-form-urlencoded` body of a slash command request.
+    /// The body is not verified, see `from_request`.
+    pub fn from_form(body: &[u8]) -> Result<SlashCommand, Error> {
+        SlashCommand::from_fields(form_urlencoded::parse(body).into_owned().collect())
+    }
+
+    /// Verifies a slash command request with signing_secret against the values of the
+    /// `X-Slack-Request-Timestamp` and `X-Slack-Signature` headers, then parses its body.
+    pub fn from_request(signing_secret: &str, timestamp: &str, signature: &str, body: &[u8]) -> Result<SlashCommand, Error> {
+        try!(signature::verify(signing_secret, timestamp, signature, body));
+        SlashCommand::from_form(body)
+    }
+
+    /// Reads a slash command from the payload of a `slash_commands` Socket Mode envelope.
+    pub fn from_payload(payload: &Json) -> Result<SlashCommand, Error> {
+        let fields = match payload.as_object() {
+            Some(object) => {
+                object.iter()
+                      .filter_map(|(k, v)| v.as_string().map(|v| (k.clone(), v.to_string())))
+                      .collect()
+            }
+            None => return Err(Error::Internal(String::from("slash command payload is not an object"))),
+        };
+        SlashCommand::from_fields(fields)
+    }
+
+    /// Reads a slash command from a Socket Mode envelope.
+    /// The envelope accepts a response payload, so a `SlashResponse` can be returned from
+    /// `SocketModeHandler::on_envelope` to reply right away.
+    pub fn from_envelope(envelope: &Envelope) -> Result<SlashCommand, Error> {
+        match (&envelope.kind[..], envelope.payload.as_ref()) {
+            ("slash_commands", Some(payload)) => SlashCommand::from_payload(payload),
+            _ => Err(Error::Internal(format!("not a slash command envelope: {}", envelope.kind))),
+        }
+    }
+
+    /// Replies to the command through its `response_url`, which can be done up to five
+    /// times in the 30 minutes after the command.
+    pub fn respond_later(&self, response: &SlashResponse) -> Result<(), Error> {
+        let body = try!(response.to_json());
+        let client = hyper::Client::new();
+        web::post_json(&client, &self.response_url, &body)
+    }
+}
+
+/// Who sees the reply to a slash command.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResponseType {
+    /// Only the user that sent the command.
+    Ephemeral,
+    /// Everyone in the channel, along with the command.
+    InChannel,
+}
+
+impl ResponseType {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            ResponseType::Ephemeral => "ephemeral",
+            ResponseType::InChannel => "in_channel",
+        }
+    }
+}
+
+/// A reply to a slash command, sent either as the body of the HTTP response to the
+/// command, as the payload of its Socket Mode acknowledgement, or with
+/// `SlashCommand::respond_later`.
+///
+/// ```
+/// let reply = slack::slash::SlashResponse::in_channel("Standup starts now!");
+/// assert_eq!(reply.to_body().unwrap(),
+///            r#"{"response_type":"in_channel","text":"Standup starts now!"}"#);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlashResponse {
+    response_type: ResponseType,
+    text: String,
+    attachments: Option<String>,
+    blocks: Option<String>,
+}
+
+impl SlashResponse {
+    /// Creates a reply of response_type with the given text.
+    pub fn new(response_type: ResponseType, text: &str) -> SlashResponse {
+        SlashResponse {
+            response_type: response_type,
+            text: text.to_string(),
+            attachments: None,
+            blocks: None,
+        }
+    }
+
+    /// Creates a reply only the user that sent the command sees.
+    pub fn ephemeral(text: &str) -> SlashResponse {
+        SlashResponse::new(ResponseType::Ephemeral, text)
+    }
+
+    /// Creates a reply everyone in the channel sees.
+    pub fn in_channel(text: &str) -> SlashResponse {
+        SlashResponse::new(ResponseType::InChannel, text)
+    }
+
+    /// Sets the attachments, as a json array.
+    pub fn attachments(mut self, attachments: &str) -> SlashResponse {
+        self.attachments = Some(attachments.to_string());
+        self
+    }
+
+    /// Sets the blocks, as a json array.
+    pub fn blocks(mut self, blocks: &str) -> SlashResponse {
+        self.blocks = Some(blocks.to_string());
+        self
+    }
+
+    /// Returns the reply as json, failing if the attachments or blocks aren't valid json.
+    pub fn to_json(&self) -> Result<Json, Error> {
+        let mut json = BTreeMap::new();
+        json.insert(String::from("response_type"), Json::String(self.response_type.as_str().to_string()));
+        json.insert(String::from("text"), Json::String(self.text.clone()));
+        if let Some(ref attachments) = self.attachments {
+            json.insert(String::from("attachments"), try!(Json::from_str(attachments)));
+        }
+        if let Some(ref blocks) = self.blocks {
+            json.insert(String::from("blocks"), try!(Json::from_str(blocks)));
+        }
+        Ok(Json::Object(json))
+    }
+
+    /// Returns the reply as the json body of the HTTP response to a command, which is sent
+    /// with a `Content-Type` of `application/json`.
+    pub fn to_body(&self) -> Result<String, Error> {
+        Ok(try!(self.to_json()).to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustc_serialize::json::Json;
+
+    const FORM: &'static str = "token=gIkuvaNzQIHg97ATvDxqgjtO&team_id=T0001&team_domain=example&\
+                                enterprise_id=E0001&channel_id=C2147483705&channel_name=test&\
+                                user_id=U2147483697&user_name=Steve&command=%2Fweather&text=94070&\
+                                response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2F1234%2F5678&\
+                                trigger_id=13345224609.738474920.8088930838d88f008e0&api_app_id=A123456";
+
+    #[test]
+    fn parse_form() {
+        let command = SlashCommand::from_form(FORM.as_bytes()).unwrap();
+        assert_eq!(command.team_id, "T0001");
+        assert_eq!(command.channel_id, "C2147483705");
+        assert_eq!(command.user_id, "U2147483697");
+        assert_eq!(command.command, "/weather");
+        assert_eq!(command.text, "94070");
+        assert_eq!(command.response_url, "https://hooks.slack.com/commands/1234/5678");
+        assert_eq!(command.trigger_id, "13345224609.738474920.8088930838d88f008e0");
+        assert_eq!(command.user_name, Some(String::from("Steve")));
+    }
+
+    #[test]
+    fn parse_payload_like_form() {
+        let payload = Json::from_str(r#"{
+            "team_id": "T0001", "team_domain": "example", "enterprise_id": "E0001",
+            "channel_id": "C2147483705", "channel_name": "test", "user_id": "U2147483697",
+            "user_name": "Steve", "command": "/weather", "text": "94070",
+            "response_url": "https://hooks.slack.com/commands/1234/5678",
+            "trigger_id": "13345224609.738474920.8088930838d88f008e0", "api_app_id": "A123456"
+        }"#)
+                          .unwrap();
+        let expected = SlashCommand::from_form(FORM.as_bytes()).unwrap();
+        assert_eq!(SlashCommand::from_payload(&payload).unwrap(), expected);
+    }
+
+    #[test]
+    fn missing_fields_fail() {
+        assert!(SlashCommand::from_form(b"team_id=T0001&text=hi").is_err());
+    }
+
+    #[test]
+    fn ephemeral_response() {
+        let reply = SlashResponse::ephemeral("Only you").attachments(r#"[{"text": "details"}]"#);
+        assert_eq!(reply.to_body().unwrap(),
+                   r#"{"attachments":[{"text":"details"}],"response_type":"ephemeral","text":"Only you"}"#);
+        assert!(SlashResponse::ephemeral("x").blocks("[").to_json().is_err());
+    }
+}
@@ -0,0 +1,354 @@
+//
+// Copyright 2014-2016 the slack-rs authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Receiving Events API payloads, slash commands and interactions over a websocket with
+//! Socket Mode, see https://api.slack.com/apis/connections/socket
+//!
+//! Socket Mode needs no public request url. The connection is opened with an app-level
+//! token, while the token of the `RtmClient` is still used for Web API calls.
+
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, channel};
+use std::thread;
+
+use hyper;
+use rustc_serialize::json::{self, Json};
+use websocket::client::Receiver as WsReceiver;
+use websocket::message::Type as WsType;
+use websocket::stream::WebSocketStream;
+
+use error::Error;
+use events::Event;
+use web;
+use {close_result, close_status, connect_websocket, join_sender, receive, sender_closed, set_read_timeout,
+     spawn_sender, websocket_error};
+use {EventHandler, RtmClient, WebSocketMessage, WsClient, WsMessage};
+
+/// A message received over a Socket Mode connection.
+#[derive(Clone, Debug)]
+pub struct Envelope {
+    /// `hello`, `events_api`, `slash_commands`, `interactive` or `disconnect`.
+    pub kind: String,
+    /// The id to acknowledge the envelope with, not set for `hello` and `disconnect`.
+    pub envelope_id: Option<String>,
+    /// The Events API, slash command or interaction payload.
+    pub payload: Option<Json>,
+    /// Whether a payload can be sent back with the acknowledgement.
+    pub accepts_response_payload: bool,
+    /// How many times slack already tried to deliver the envelope.
+    pub retry_attempt: Option<u64>,
+    /// Why slack is closing the connection, for `disconnect`: `warning`,
+    /// `refresh_requested` or `link_disabled`.
+    pub reason: Option<String>,
+}
+
+impl Envelope {
+    /// Parses an envelope from the text of a websocket message.
+    pub fn from_json(raw_json: &str) -> Result<Envelope, Error> {
+        let json = try!(Json::from_str(raw_json));
+        let kind = match json.find("type").and_then(|t| t.as_string()) {
+            Some(kind) => kind.to_string(),
+            None => return Err(Error::Internal(String::from("socket mode message without type"))),
+        };
+        let string = |field: &str| json.find(field).and_then(|v| v.as_string()).map(|v| v.to_string());
+        Ok(Envelope {
+            kind: kind,
+            envelope_id: string("envelope_id"),
+            payload: json.find("payload").cloned(),
+            accepts_response_payload: json.find("accepts_response_payload")
+                                          .and_then(|a| a.as_boolean())
+                                          .unwrap_or(false),
+            retry_attempt: json.find("retry_attempt").and_then(|r| r.as_u64()),
+            reason: string("reason"),
+        })
+    }
+}
+
+/// Implement this trait in your code to handle Socket Mode envelopes.
+/// Besides `on_envelope`, `on_connect` is called for each `hello`, `on_ping` for each
+/// ping and `on_close` when the connection is closed.
+pub trait SocketModeHandler: EventHandler {
+    /// Called for every envelope that needs to be acknowledged. The default passes the
+    /// event of `events_api` envelopes to `on_event` and ignores other envelopes.
+    ///
+    /// The returned payload, e.g. a slash command reply, is sent with the acknowledgement
+    /// if the envelope accepts a response payload. Envelopes that don't are acknowledged
+    /// before this is called, and envelopes without an id are never acknowledged.
+    fn on_envelope(&mut self, cli: &mut RtmClient, envelope: &Envelope) -> Option<Json> {
+        if envelope.kind == "events_api" {
+            if let Some(event) = envelope.payload.as_ref().and_then(|p| p.find("event")) {
+                let raw_json = event.to_string();
+                let event = json::decode::<Event>(&raw_json).map_err(|e| e.into());
+                self.on_event(cli, event, &raw_json);
+            }
+        }
+        None
+    }
+}
+
+#[derive(RustcDecodable)]
+struct OpenResponse {
+    url: String,
+}
+
+/// Uses https://api.slack.com/methods/apps.connections.open to open a Socket Mode
+/// connection with app_token, an app-level token with the `connections:write` scope.
+pub fn open_connection(app_token: &str) -> Result<WsClient, Error> {
+    let client = hyper::Client::new();
+    let res: OpenResponse = try!(web::call(&client, "apps.connections.open", &[("token", app_token)]));
+    let wss_url = try!(hyper::Url::parse(&res.url).map_err(|e| hyper::Error::Uri(e)));
+    connect_websocket(wss_url)
+}
+
+/// Returns the text of the acknowledgement of an envelope.
+fn acknowledgement(envelope_id: &str, payload: Option<Json>) -> String {
+    let mut ack = BTreeMap::new();
+    ack.insert(String::from("envelope_id"), Json::String(envelope_id.to_string()));
+    if let Some(payload) = payload {
+        ack.insert(String::from("payload"), payload);
+    }
+    Json::Object(ack).to_string()
+}
+
+/// Shuts down the sender and receiver of a connection and joins the sender thread,
+/// returning the error it ended with, if any.
+fn shutdown(tx: &mpsc::Sender<WsMessage>,
+            receiver: &mut WsReceiver<WebSocketStream>,
+            child: thread::JoinHandle<()>,
+            errors: &mpsc::Receiver<Error>)
+            -> Option<Error> {
+    let _ = tx.send(WsMessage::Close);
+    let _ = receiver.shutdown_all();
+    join_sender(child, errors)
+}
+
+impl RtmClient {
+    /// Passes an envelope to handler and acknowledges it over tx, before calling the handler
+    /// unless a payload can be sent back. Envelopes without an id can't be acknowledged.
+    fn handle_envelope<T: SocketModeHandler>(&mut self,
+                                             handler: &mut T,
+                                             tx: &mpsc::Sender<WsMessage>,
+                                             envelope: &Envelope) {
+        let envelope_id = envelope.envelope_id.as_ref().map_or("", |id| &id[..]);
+        let acknowledge = !envelope_id.is_empty();
+        if acknowledge && !envelope.accepts_response_payload {
+            let _ = tx.send(WsMessage::Text(acknowledgement(envelope_id, None)));
+        }
+        let response = handler.on_envelope(self, envelope);
+        if acknowledge && envelope.accepts_response_payload {
+            let _ = tx.send(WsMessage::Text(acknowledgement(envelope_id, response)));
+        }
+    }
+
+    /// Runs the Socket Mode receive loop, opening a connection with app_token and passing
+    /// the envelopes received to handler. When slack asks to disconnect, a new connection is
+    /// opened, unless the app was disabled.
+    /// `RtmClient::shutdown` and `RtmClient::shutdown_gracefully` close the open connection,
+    /// as with `RtmClient::run`.
+    /// Returns an error if the process fails at any point, or an Ok(()) on successful close,
+    /// as with `RtmClient::run`.
+    pub fn run_socket_mode<T: SocketModeHandler>(&mut self, app_token: &str, handler: &mut T) -> Result<(), Error> {
+        loop {
+            let client = try!(open_connection(app_token));
+            match try!(self.run_socket_connection(handler, client)) {
+                Some(ref reason) if reason != "link_disabled" => {}
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Runs the receive loop of a single Socket Mode connection.
+    /// Returns the reason given by slack when it asks to disconnect, or None if the
+    /// connection was closed normally.
+    fn run_socket_connection<T: SocketModeHandler>(&mut self,
+                                                   handler: &mut T,
+                                                   client: WsClient)
+                                                   -> Result<Option<String>, Error> {
+        let (sender, mut receiver) = client.split();
+        let (tx, rx) = channel::<WsMessage>();
+        let (errors_tx, errors) = channel::<Error>();
+        let child = spawn_sender(sender, rx, errors_tx);
+        // so that `shutdown`, `shutdown_gracefully` and `channel` use this connection
+        self.set_outs(tx.clone());
+        if let Err(err) = set_read_timeout(&mut receiver) {
+            let _ = shutdown(&tx, &mut receiver, child, &errors);
+            return Err(err);
+        }
+
+        loop {
+            let message: WebSocketMessage = match receive(&mut receiver) {
+                Ok(message) => message,
+                Err(err) => {
+                    return match shutdown(&tx, &mut receiver, child, &errors) {
+                        Some(err) => Err(err),
+                        None if self.closed_locally() => Ok(None),
+                        None => Err(websocket_error(err)),
+                    };
+                }
+            };
+            match message.opcode {
+                WsType::Text => {
+                    let raw_string = match String::from_utf8(message.payload.into_owned()) {
+                        Ok(raw_string) => raw_string,
+                        Err(err) => {
+                            let _ = shutdown(&tx, &mut receiver, child, &errors);
+                            return Err(Error::Utf8(err));
+                        }
+                    };
+                    let envelope = match Envelope::from_json(&raw_string) {
+                        Ok(envelope) => envelope,
+                        Err(err) => {
+                            handler.on_event(self, Err(err), &raw_string);
+                            continue;
+                        }
+                    };
+                    match &envelope.kind[..] {
+                        "hello" => handler.on_connect(self),
+                        "disconnect" => {
+                            let _ = shutdown(&tx, &mut receiver, child, &errors);
+                            return Ok(Some(envelope.reason.unwrap_or_default()));
+                        }
+                        _ => self.handle_envelope(handler, &tx, &envelope),
+                    }
+                }
+                WsType::Ping => {
+                    handler.on_ping(self);
+                    let raw_string = match String::from_utf8(message.payload.into_owned()) {
+                        Ok(raw_string) => raw_string,
+                        Err(err) => {
+                            let _ = shutdown(&tx, &mut receiver, child, &errors);
+                            return Err(Error::Utf8(err));
+                        }
+                    };
+                    if tx.send(WsMessage::Pong(raw_string)).is_err() {
+                        let sender_err = shutdown(&tx, &mut receiver, child, &errors);
+                        return Err(sender_err.unwrap_or_else(sender_closed));
+                    }
+                }
+                WsType::Close => {
+                    let (code, reason) = close_status(&message.payload);
+                    handler.on_close(self, code, &reason);
+                    return match shutdown(&tx, &mut receiver, child, &errors) {
+                        Some(err) => Err(err),
+                        None => close_result(code, reason, self.closed_locally()).map(|_| None),
+                    };
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Counts the envelopes it is called with and replies to each of them.
+    struct Replier {
+        envelopes: usize,
+    }
+
+    impl EventHandler for Replier {
+        fn on_event(&mut self, _: &mut RtmClient, _: Result<Event, Error>, _: &str) {}
+        fn on_ping(&mut self, _: &mut RtmClient) {}
+        fn on_close(&mut self, _: &mut RtmClient, _: Option<u16>, _: &str) {}
+        fn on_connect(&mut self, _: &mut RtmClient) {}
+    }
+
+    impl SocketModeHandler for Replier {
+        fn on_envelope(&mut self, _: &mut RtmClient, _: &Envelope) -> Option<Json> {
+            self.envelopes += 1;
+            Some(Json::from_str(r#"{"text": "hi"}"#).unwrap())
+        }
+    }
+
+    fn handle(raw_json: &str) -> (Replier, Vec<String>) {
+        let mut cli = RtmClient::new("xapp-test");
+        let mut handler = Replier { envelopes: 0 };
+        let (tx, rx) = channel();
+        let envelope = Envelope::from_json(raw_json).unwrap();
+        cli.handle_envelope(&mut handler, &tx, &envelope);
+        let acks = rx.try_iter()
+                     .map(|message| {
+                         match message {
+                             WsMessage::Text(text) => text,
+                             _ => panic!("Envelope was not acknowledged with text."),
+                         }
+                     })
+                     .collect();
+        (handler, acks)
+    }
+
+    #[test]
+    fn acknowledge_envelopes() {
+        let (handler, acks) = handle(r#"{"envelope_id": "abc", "type": "events_api", "payload": {}}"#);
+        assert_eq!(handler.envelopes, 1);
+        assert_eq!(acks, vec![r#"{"envelope_id":"abc"}"#]);
+
+        let (handler, acks) = handle(r#"{
+            "envelope_id": "abc",
+            "type": "slash_commands",
+            "payload": {},
+            "accepts_response_payload": true
+        }"#);
+        assert_eq!(handler.envelopes, 1);
+        assert_eq!(acks, vec![r#"{"envelope_id":"abc","payload":{"text":"hi"}}"#]);
+    }
+
+    #[test]
+    fn envelopes_without_id_are_not_acknowledged() {
+        let (handler, acks) = handle(r#"{"type": "events_api", "payload": {}}"#);
+        assert_eq!(handler.envelopes, 1);
+        assert!(acks.is_empty());
+
+        let (handler, acks) = handle(r#"{"envelope_id": "", "type": "interactive", "accepts_response_payload": true}"#);
+        assert_eq!(handler.envelopes, 1);
+        assert!(acks.is_empty());
+    }
+
+    #[test]
+    fn parse_events_api_envelope() {
+        let envelope = Envelope::from_json(r#"{
+            "envelope_id": "57d6a792-4d35-4d0b-b6aa-3361493e1caf",
+            "payload": {"type": "event_callback", "event": {"type": "pref_change"}},
+            "type": "events_api",
+            "accepts_response_payload": false,
+            "retry_attempt": 1
+        }"#)
+                           .unwrap();
+        assert_eq!(envelope.kind, "events_api");
+        assert_eq!(envelope.envelope_id, Some(String::from("57d6a792-4d35-4d0b-b6aa-3361493e1caf")));
+        assert!(!envelope.accepts_response_payload);
+        assert_eq!(envelope.retry_attempt, Some(1));
+        assert!(envelope.payload.unwrap().find("event").is_some());
+    }
+
+    #[test]
+    fn parse_disconnect() {
+        let envelope = Envelope::from_json(r#"{"type": "disconnect", "reason": "refresh_requested"}"#).unwrap();
+        assert_eq!(envelope.kind, "disconnect");
+        assert_eq!(envelope.envelope_id, None);
+        assert_eq!(envelope.reason, Some(String::from("refresh_requested")));
+    }
+
+    #[test]
+    fn acknowledge_with_payload() {
+        let payload = Json::from_str(r#"{"text": "hi"}"#).unwrap();
+        assert_eq!(acknowledgement("abc", None), r#"{"envelope_id":"abc"}"#);
+        assert_eq!(acknowledgement("abc", Some(payload)),
+                   r#"{"envelope_id":"abc","payload":{"text":"hi"}}"#);
+    }
+}
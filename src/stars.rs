@@ -0,0 +1,134 @@
+//
+// Copyright 2014-2016 the slack-rs authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Starring and un-starring items for the connected user.
+
+use hyper;
+use rustc_serialize::json::Json;
+
+use error::Error;
+use events::{self, Item};
+use files::Paging;
+use web;
+use RtmClient;
+
+/// Response of https://api.slack.com/methods/stars.list
+#[derive(Clone, Debug, RustcDecodable)]
+pub struct ListResponse {
+    pub items: Vec<Item>,
+    pub paging: Option<Paging>,
+}
+
+impl ListResponse {
+    /// Decodes a response, leaving out the items of types that `Item` doesn't know.
+    fn from_json(json: &Json) -> Result<ListResponse, Error> {
+        let paging = match json.find("paging") {
+            Some(paging) if !paging.is_null() => Some(try!(web::decode(paging.clone()))),
+            _ => None,
+        };
+        Ok(ListResponse {
+            items: try!(web::decode_typed_items(json, "items", events::ITEM_TYPES)),
+            paging: paging,
+        })
+    }
+}
+
+impl RtmClient {
+    /// Calls a stars method for the item.
+    fn call_stars(&self, method: &str, item: &Item) -> Result<(), Error> {
+        let mut params = vec![("token", &self.token[..])];
+        params.extend(item.api_params());
+
+        let client = hyper::Client::new();
+        try!(web::call_json(&client, method, &params));
+        Ok(())
+    }
+
+    /// Wraps https://api.slack.com/methods/stars.add to star a message, file, file comment
+    /// or channel for the connected user.
+    pub fn add_star(&self, item: &Item) -> Result<(), Error> {
+        self.call_stars("stars.add", item)
+    }
+
+    /// Wraps https://api.slack.com/methods/stars.remove to remove the star of the connected
+    /// user from a message, file, file comment or channel.
+    pub fn remove_star(&self, item: &Item) -> Result<(), Error> {
+        self.call_stars("stars.remove", item)
+    }
+
+    /// Wraps https://api.slack.com/methods/stars.list to list the items starred by the
+    /// connected user. Items of types that `Item` doesn't know are left out.
+    pub fn list_stars(&self, count: Option<u32>, page: Option<u32>) -> Result<ListResponse, Error> {
+        let count = count.map(|c| c.to_string());
+        let page = page.map(|p| p.to_string());
+        let mut params = vec![("token", &self.token[..])];
+        web::push_opt(&mut params, "count", count.as_ref().map(|c| &c[..]));
+        web::push_opt(&mut params, "page", page.as_ref().map(|p| &p[..]));
+
+        let client = hyper::Client::new();
+        let json = try!(web::call_json(&client, "stars.list", &params));
+        ListResponse::from_json(&json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_starred_items() {
+        let json = Json::from_str(r#"{
+            "ok": true,
+            "items": [
+                {
+                    "type": "message",
+                    "channel": "C123",
+                    "message": {"type": "message", "text": "hi", "ts": "1360782400.498405"}
+                },
+                {"type": "file", "file": {"id": "F123", "name": "notes.txt"}},
+                {"type": "file_comment", "file": {"id": "F123"}, "comment": {"id": "Fc123", "comment": "nice"}},
+                {"type": "channel", "channel": "C123"},
+                {"type": "im", "channel": "D123"},
+                {"type": "group", "channel": "G123"},
+                {"type": "app", "app": {"id": "A123"}}
+            ],
+            "paging": {"count": 100, "total": 7, "page": 1, "pages": 1}
+        }"#).unwrap();
+        let response = ListResponse::from_json(&json).unwrap();
+        assert_eq!(response.items,
+                   vec![Item::Message {
+                            channel: String::from("C123"),
+                            ts: String::from("1360782400.498405"),
+                        },
+                        Item::File { file: String::from("F123") },
+                        Item::FileComment {
+                            file: Some(String::from("F123")),
+                            comment: String::from("Fc123"),
+                        },
+                        Item::Channel { channel: String::from("C123") },
+                        Item::Im { channel: String::from("D123") },
+                        Item::Group { channel: String::from("G123") }]);
+        assert_eq!(response.paging.unwrap().total, 7);
+    }
+
+    #[test]
+    fn decode_without_paging() {
+        let json = Json::from_str(r#"{"ok": true, "items": [{"type": "app", "app": {"id": "A123"}}]}"#).unwrap();
+        let response = ListResponse::from_json(&json).unwrap();
+        assert!(response.items.is_empty());
+        assert!(response.paging.is_none());
+    }
+}
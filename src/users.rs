@@ -0,0 +1,282 @@
+//
+// Copyright 2014-2016 the slack-rs authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Looking up users and reading or changing their profile and presence.
+
+use std::collections::BTreeMap;
+
+use hyper;
+use rustc_serialize::json::Json;
+
+use api::User;
+use error::Error;
+use web;
+use RtmClient;
+
+/// A user's profile, see https://api.slack.com/types/user
+#[derive(Clone, Debug, RustcDecodable)]
+pub struct Profile {
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub real_name: Option<String>,
+    pub display_name: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub title: Option<String>,
+    pub status_text: Option<String>,
+    pub status_emoji: Option<String>,
+    pub image_24: Option<String>,
+    pub image_48: Option<String>,
+    pub image_72: Option<String>,
+    pub image_192: Option<String>,
+}
+
+/// A user's presence.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Presence {
+    /// Slack decides the presence based on activity.
+    Auto,
+    /// Marked as away.
+    Away,
+}
+
+impl Presence {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            Presence::Auto => "auto",
+            Presence::Away => "away",
+        }
+    }
+}
+
+/// Response of https://api.slack.com/methods/users.getPresence
+/// The fields besides presence are only set when asking for the connected user.
+#[derive(Clone, Debug, RustcDecodable)]
+pub struct PresenceResponse {
+    /// `active` or `away`.
+    pub presence: String,
+    pub online: Option<bool>,
+    pub auto_away: Option<bool>,
+    pub manual_away: Option<bool>,
+    pub connection_count: Option<u32>,
+    pub last_activity: Option<i64>,
+}
+
+/// Replaces cached with value if it is set.
+fn update(cached: &mut Option<String>, value: &Option<String>) {
+    if value.is_some() {
+        *cached = value.clone();
+    }
+}
+
+#[derive(RustcDecodable)]
+struct UserResponse {
+    user: User,
+}
+
+#[derive(RustcDecodable)]
+struct ProfileResponse {
+    profile: Profile,
+}
+
+impl RtmClient {
+    /// Get a user from the users of the client by id.
+    /// Only valid after login.
+    pub fn get_user(&self, user_id: &str) -> Option<User> {
+        self.state().users.iter().find(|u| u.id == user_id).cloned()
+    }
+
+    /// Adds the user to the users of the client, or replaces the user with the same id.
+    fn cache_user(&self, user: &User) {
+        let mut state = self.state_mut();
+        match state.users.iter().position(|u| u.id == user.id) {
+            Some(i) => {
+                let old_name = state.users[i].name.clone();
+                state.user_ids.remove(&old_name);
+                state.users[i] = user.clone();
+            }
+            None => state.users.push(user.clone()),
+        }
+        state.user_ids.insert(user.name.clone(), user.id.clone());
+    }
+
+    /// Updates the profile of the user with user_id in the users of the client, if it is
+    /// one of them, with the fields that profile has.
+    fn cache_profile(&self, user_id: &str, profile: &Profile) {
+        let mut state = self.state_mut();
+        if let Some(user) = state.users.iter_mut().find(|u| u.id == user_id) {
+            let cached = &mut user.profile;
+            update(&mut cached.first_name, &profile.first_name);
+            update(&mut cached.last_name, &profile.last_name);
+            update(&mut cached.real_name, &profile.real_name);
+            update(&mut cached.email, &profile.email);
+            update(&mut cached.phone, &profile.phone);
+            if let Some(ref image) = profile.image_24 {
+                cached.image_24 = image.clone();
+            }
+            if let Some(ref image) = profile.image_48 {
+                cached.image_48 = image.clone();
+            }
+            if let Some(ref image) = profile.image_72 {
+                cached.image_72 = image.clone();
+            }
+            if let Some(ref image) = profile.image_192 {
+                cached.image_192 = image.clone();
+            }
+        }
+    }
+
+    /// Returns user_id, or the id of the connected user if it is None.
+    fn profile_owner(&self, user_id: Option<&str>) -> Option<String> {
+        user_id.map(|u| u.to_string()).or_else(|| self.get_id())
+    }
+
+    /// Wraps https://api.slack.com/methods/users.info to fetch a single user, which is also
+    /// updated in the users of the client.
+    pub fn user_info(&self, user_id: &str) -> Result<User, Error> {
+        let client = hyper::Client::new();
+        let res: UserResponse = try!(web::call(&client,
+                                               "users.info",
+                                               &[("token", &self.token[..]), ("user", user_id)]));
+        self.cache_user(&res.user);
+        Ok(res.user)
+    }
+
+    /// Wraps https://api.slack.com/methods/users.lookupByEmail to find a user by their email
+    /// address, who is also updated in the users of the client.
+    pub fn lookup_by_email(&self, email: &str) -> Result<User, Error> {
+        let client = hyper::Client::new();
+        let res: UserResponse = try!(web::call(&client,
+                                               "users.lookupByEmail",
+                                               &[("token", &self.token[..]), ("email", email)]));
+        self.cache_user(&res.user);
+        Ok(res.user)
+    }
+
+    /// Wraps https://api.slack.com/methods/users.profile.get to get the profile of a user,
+    /// or of the connected user if user_id is None. The profile is also updated in the users
+    /// of the client.
+    pub fn get_profile(&self, user_id: Option<&str>) -> Result<Profile, Error> {
+        let mut params = vec![("token", &self.token[..])];
+        web::push_opt(&mut params, "user", user_id);
+
+        let client = hyper::Client::new();
+        let res: ProfileResponse = try!(web::call(&client, "users.profile.get", &params));
+        if let Some(owner) = self.profile_owner(user_id) {
+            self.cache_profile(&owner, &res.profile);
+        }
+        Ok(res.profile)
+    }
+
+    /// Wraps https://api.slack.com/methods/users.profile.set to set the given profile fields
+    /// (e.g. `("status_text", "lunch")`) of a user, or of the connected user if user_id is None.
+    /// Returns the updated profile, which is also updated in the users of the client.
+    pub fn set_profile(&self, user_id: Option<&str>, fields: &[(&str, &str)]) -> Result<Profile, Error> {
+        let profile: BTreeMap<String, Json> = fields.iter()
+                                                    .map(|&(k, v)| (k.to_string(), Json::String(v.to_string())))
+                                                    .collect();
+        let profile = Json::Object(profile).to_string();
+        let mut params = vec![("token", &self.token[..]), ("profile", &profile[..])];
+        web::push_opt(&mut params, "user", user_id);
+
+        let client = hyper::Client::new();
+        let res: ProfileResponse = try!(web::call(&client, "users.profile.set", &params));
+        if let Some(owner) = self.profile_owner(user_id) {
+            self.cache_profile(&owner, &res.profile);
+        }
+        Ok(res.profile)
+    }
+
+    /// Wraps https://api.slack.com/methods/users.getPresence to get the presence of a user.
+    pub fn get_presence(&self, user_id: &str) -> Result<PresenceResponse, Error> {
+        let client = hyper::Client::new();
+        web::call(&client,
+                  "users.getPresence",
+                  &[("token", &self.token[..]), ("user", user_id)])
+    }
+
+    /// Wraps https://api.slack.com/methods/users.setPresence to set the presence of the
+    /// connected user.
+    pub fn set_presence(&self, presence: Presence) -> Result<(), Error> {
+        let client = hyper::Client::new();
+        try!(web::call_json(&client,
+                            "users.setPresence",
+                            &[("token", &self.token[..]), ("presence", presence.as_str())]));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustc_serialize::json::Json;
+
+    fn user(id: &str, name: &str, real_name: &str) -> User {
+        let json = format!(r#"{{"id": "{}", "name": "{}", "deleted": false, "profile": {{
+            "real_name": "{}", "image_24": "a", "image_32": "b", "image_48": "c",
+            "image_72": "d", "image_192": "e"}}}}"#,
+                           id,
+                           name,
+                           real_name);
+        web::decode(Json::from_str(&json).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn cache_user_insert_and_update() {
+        let cli = RtmClient::new("token");
+        cli.cache_user(&user("U1", "bob", "Bob"));
+        assert_eq!(cli.get_user_id("bob"), Some(String::from("U1")));
+        assert_eq!(cli.get_user("U1").unwrap().profile.real_name, Some(String::from("Bob")));
+
+        cli.cache_user(&user("U1", "bob", "Robert"));
+        assert_eq!(cli.state().users.len(), 1);
+        assert_eq!(cli.get_user("U1").unwrap().profile.real_name, Some(String::from("Robert")));
+
+        cli.clone().cache_user(&user("U2", "alice", "Alice"));
+        assert_eq!(cli.state().users.len(), 2);
+        assert_eq!(cli.get_user_id("alice"), Some(String::from("U2")));
+    }
+
+    #[test]
+    fn cache_user_rename() {
+        let cli = RtmClient::new("token");
+        cli.cache_user(&user("U1", "bob", "Bob"));
+        cli.cache_user(&user("U1", "robert", "Bob"));
+        assert_eq!(cli.get_user_id("bob"), None);
+        assert_eq!(cli.get_user_id("robert"), Some(String::from("U1")));
+        assert_eq!(cli.get_user("U1").unwrap().name, "robert");
+    }
+
+    #[test]
+    fn cache_profile_updates_cached_user() {
+        let cli = RtmClient::new("token");
+        cli.cache_user(&user("U1", "bob", "Bob"));
+        let profile: Profile = web::decode(Json::from_str(r#"{"real_name": "Robert", "image_48": "new",
+                                                             "status_text": "lunch"}"#)
+                                               .unwrap())
+                                   .unwrap();
+        cli.cache_profile("U1", &profile);
+        cli.cache_profile("U2", &profile);
+
+        let cached = cli.get_user("U1").unwrap().profile;
+        assert_eq!(cached.real_name, Some(String::from("Robert")));
+        assert_eq!(cached.image_48, "new");
+        assert_eq!(cached.image_24, "a");
+        assert_eq!(cli.state().users.len(), 1);
+        assert_eq!(cli.profile_owner(Some("U1")), Some(String::from("U1")));
+        assert_eq!(cli.profile_owner(None), None);
+    }
+}
@@ -0,0 +1,252 @@
+//
+// Copyright 2014-2016 the slack-rs authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Direct calls to Web API methods that are not wrapped by `slack_api`.
+
+use std::collections::HashMap;
+use std::io::Read;
+
+use hyper;
+use hyper::header::ContentType;
+use rustc_serialize::Decodable;
+use rustc_serialize::json::{self, Json};
+use url::form_urlencoded;
+
+use error::Error;
+
+/// Base url of the slack Web API.
+pub const API_BASE: &'static str = "https://slack.com/api/";
+
+/// Calls the Web API method with form encoded params and decodes the response into T.
+pub fn call<T: Decodable>(client: &hyper::Client, method: &str, params: &[(&str, &str)]) -> Result<T, Error> {
+    let json = try!(call_json(client, method, params));
+    decode(json)
+}
+
+/// Calls the Web API method with form encoded params and returns the raw json response.
+/// Responses with `"ok": false` are turned into an `Error::Api` holding the error code
+/// and the HTTP status.
+pub fn call_json(client: &hyper::Client, method: &str, params: &[(&str, &str)]) -> Result<Json, Error> {
+    let body = form_urlencoded::Serializer::new(String::new())
+                   .extend_pairs(params.iter())
+                   .finish();
+    let url = format!("{}{}", API_BASE, method);
+    let res = try!(client.post(&url)
+                         .header(ContentType::form_url_encoded())
+                         .body(&body[..])
+                         .send());
+    read_response(res)
+}
+
+/// Reads and checks the `ok` field of a Web API response.
+pub fn read_response(mut res: hyper::client::Response) -> Result<Json, Error> {
+    let status = res.status.to_u16();
+    let mut raw = String::new();
+    try!(res.read_to_string(&mut raw));
+    let json = match Json::from_str(&raw) {
+        Ok(json) => json,
+        // e.g. a rate limit or an outage, which may not come with a json body
+        Err(_) if !res.status.is_success() => return Err(Error::api(error_text(&raw, status), Some(status))),
+        Err(err) => return Err(err.into()),
+    };
+
+    match json.find("ok").and_then(|ok| ok.as_boolean()) {
+        Some(true) => Ok(json),
+        _ => {
+            let code = json.find("error").and_then(|e| e.as_string()).unwrap_or("unknown_error");
+            Err(Error::api(code, Some(status)))
+        }
+    }
+}
+
+/// Returns the error code of a plain text error response such as `channel_not_found`.
+/// Bodies that don't look like an error code, such as empty bodies or html error pages,
+/// fall back to a code for the HTTP status.
+fn error_text(raw: &str, status: u16) -> &str {
+    let raw = raw.trim();
+    let is_code = !raw.is_empty() && raw.len() <= 64 &&
+                  raw.chars().all(|c| (c >= 'a' && c <= 'z') || (c >= '0' && c <= '9') || c == '_');
+    match status {
+        _ if is_code => raw,
+        429 => "ratelimited",
+        _ if status >= 500 => "service_unavailable",
+        _ => "unknown_error",
+    }
+}
+
+/// Decodes a json value into T.
+pub fn decode<T: Decodable>(json: Json) -> Result<T, Error> {
+    let mut decoder = json::Decoder::new(json);
+    Ok(try!(Decodable::decode(&mut decoder)))
+}
+
+/// Decodes each element of the json array field of a response.
+pub fn decode_items<T: Decodable>(json: &Json, field: &str) -> Result<Vec<T>, Error> {
+    let mut items = Vec::new();
+    if let Some(array) = json.find(field).and_then(|a| a.as_array()) {
+        for item in array.iter() {
+            items.push(try!(decode(item.clone())));
+        }
+    }
+    Ok(items)
+}
+
+/// Decodes each element of the json array field of a response like `decode_items`,
+/// skipping the elements whose `type` isn't one of types.
+pub fn decode_typed_items<T: Decodable>(json: &Json, field: &str, types: &[&str]) -> Result<Vec<T>, Error> {
+    let mut items = Vec::new();
+    if let Some(array) = json.find(field).and_then(|a| a.as_array()) {
+        for item in array.iter() {
+            let known = item.find("type").and_then(|t| t.as_string()).map_or(false, |t| types.contains(&t));
+            if known {
+                items.push(try!(decode(item.clone())));
+            }
+        }
+    }
+    Ok(items)
+}
+
+/// Returns the `response_metadata.next_cursor` of a response, if there are more pages.
+pub fn next_cursor(json: &Json) -> Option<String> {
+    json.find_path(&["response_metadata", "next_cursor"])
+        .and_then(|c| c.as_string())
+        .and_then(|c| if c.is_empty() { None } else { Some(c.to_string()) })
+}
+
+/// Adds the param to params if value is set.
+pub fn push_opt<'a>(params: &mut Vec<(&'a str, &'a str)>, name: &'a str, value: Option<&'a str>) {
+    if let Some(value) = value {
+        params.push((name, value));
+    }
+}
+
+/// Returns the string at path in json.
+pub fn string(json: &Json, path: &[&str]) -> Option<String> {
+    json.find_path(path).and_then(|v| v.as_string()).map(|v| v.to_string())
+}
+
+/// Returns the string at path in json, failing with an error naming what was being read,
+/// e.g. `interaction`, if it is missing.
+pub fn required(json: &Json, path: &[&str], what: &str) -> Result<String, Error> {
+    string(json, path).ok_or_else(|| Error::Internal(format!("{} without {}", what, path.join("."))))
+}
+
+/// Takes the field name out of the fields of a form, failing with an error naming what
+/// was being read, e.g. `slash command`, if it is missing.
+pub fn required_field(fields: &mut HashMap<String, String>, name: &str, what: &str) -> Result<String, Error> {
+    fields.remove(name).ok_or_else(|| Error::Internal(format!("{} without {}", what, name)))
+}
+
+/// Posts a json body to a url that slack gave out, such as a `response_url` or an incoming
+/// webhook. These answer `ok` on success, and either a json error response or the error
+/// code as plain text otherwise, both of which are turned into an `Error::Api`.
+pub fn post_json(client: &hyper::Client, url: &str, body: &Json) -> Result<(), Error> {
+    let body = body.to_string();
+    let mut res = try!(client.post(url)
+                             .header(ContentType::json())
+                             .body(&body[..])
+                             .send());
+    let mut raw = String::new();
+    try!(res.read_to_string(&mut raw));
+    post_json_result(res.status.to_u16(), &raw)
+}
+
+/// Reads the response of `post_json`, given its HTTP status and body.
+fn post_json_result(status: u16, raw: &str) -> Result<(), Error> {
+    let raw = raw.trim();
+    if raw == "ok" {
+        return Ok(());
+    }
+    match Json::from_str(raw) {
+        Ok(json) => {
+            match json.find("ok").and_then(|ok| ok.as_boolean()) {
+                Some(true) => Ok(()),
+                _ => {
+                    let code = json.find("error").and_then(|e| e.as_string()).unwrap_or("unknown_error");
+                    Err(Error::api(code, Some(status)))
+                }
+            }
+        }
+        Err(_) if status / 100 == 2 && raw.is_empty() => Ok(()),
+        Err(_) => Err(Error::api(error_text(raw, status), Some(status))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use error::ApiErrorCode;
+
+    fn post_error(status: u16, raw: &str) -> (String, Option<u16>) {
+        match post_json_result(status, raw) {
+            Err(err) => (err.api_code().unwrap().as_str().to_string(), err.status()),
+            Ok(_) => panic!("{} {:?} was not an error.", status, raw),
+        }
+    }
+
+    #[test]
+    fn read_required_values() {
+        let json = Json::from_str(r#"{"type": "block_actions", "user": {"id": "U123", "name": 5}}"#).unwrap();
+        assert_eq!(string(&json, &["user", "id"]), Some(String::from("U123")));
+        assert_eq!(string(&json, &["user", "name"]), None);
+        assert_eq!(required(&json, &["type"], "interaction").unwrap(), "block_actions");
+        match required(&json, &["team", "id"], "interaction") {
+            Err(Error::Internal(msg)) => assert_eq!(msg, "interaction without team.id"),
+            _ => panic!("Missing team id was not an error."),
+        }
+
+        let mut fields = HashMap::new();
+        fields.insert(String::from("team_id"), String::from("T123"));
+        assert_eq!(required_field(&mut fields, "team_id", "slash command").unwrap(), "T123");
+        assert!(fields.is_empty());
+        match required_field(&mut fields, "team_id", "slash command") {
+            Err(Error::Internal(msg)) => assert_eq!(msg, "slash command without team_id"),
+            _ => panic!("Missing team id was not an error."),
+        }
+    }
+
+    #[test]
+    fn post_json_success() {
+        assert!(post_json_result(200, "ok").is_ok());
+        assert!(post_json_result(200, "ok\n").is_ok());
+        assert!(post_json_result(200, "").is_ok());
+        assert!(post_json_result(200, r#"{"ok": true}"#).is_ok());
+    }
+
+    #[test]
+    fn post_json_plain_text_errors() {
+        assert_eq!(post_error(400, "invalid_payload"), (String::from("invalid_payload"), Some(400)));
+        assert_eq!(post_error(404, "channel_not_found\n"), (String::from("channel_not_found"), Some(404)));
+        assert_eq!(post_error(403, "action_prohibited"), (String::from("action_prohibited"), Some(403)));
+        assert_eq!(post_error(200, r#"{"ok": false, "error": "expired_url"}"#),
+                   (String::from("expired_url"), Some(200)));
+        match post_json_result(404, "channel_not_found") {
+            Err(err) => assert_eq!(err.api_code(), Some(&ApiErrorCode::ChannelNotFound)),
+            Ok(_) => panic!("Not found was not an error."),
+        }
+    }
+
+    #[test]
+    fn post_json_error_pages() {
+        let page = "<html><head><title>502 Bad Gateway</title></head></html>";
+        assert_eq!(post_error(502, page), (String::from("service_unavailable"), Some(502)));
+        assert_eq!(post_error(404, "<html><body>Not Found</body></html>"),
+                   (String::from("unknown_error"), Some(404)));
+        assert_eq!(post_error(429, ""), (String::from("ratelimited"), Some(429)));
+        assert_eq!(post_error(500, "Internal Server Error"), (String::from("service_unavailable"), Some(500)));
+        assert!(post_json_result(502, page).unwrap_err().is_retryable());
+    }
+}
@@ -0,0 +1,165 @@
+//
+// Copyright 2014-2016 the slack-rs authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Posting messages to incoming webhooks, see https://api.slack.com/messaging/webhooks
+//!
+//! Webhooks need no token, only the url slack gave out for them.
+
+use std::collections::BTreeMap;
+
+use hyper;
+use rustc_serialize::json::Json;
+
+use error::Error;
+use web;
+
+/// A message to post to a webhook. Attachments and blocks are given as json, like the
+/// attachments of `RtmClient::post_message`.
+///
+/// ```
+/// let message = slack::webhook::WebhookMessage::new("Deploy finished")
+///                   .attachments(r#"[{"color": "good", "text": "v1.2.3 is live"}]"#);
+/// assert!(message.to_json().is_ok());
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct WebhookMessage {
+    text: String,
+    attachments: Option<String>,
+    blocks: Option<String>,
+    thread_ts: Option<String>,
+    username: Option<String>,
+    icon_emoji: Option<String>,
+    icon_url: Option<String>,
+}
+
+impl WebhookMessage {
+    /// Creates a message with the given text, which is the fallback text if blocks are set.
+    pub fn new(text: &str) -> WebhookMessage {
+        WebhookMessage {
+            text: text.to_string(),
+            attachments: None,
+            blocks: None,
+            thread_ts: None,
+            username: None,
+            icon_emoji: None,
+            icon_url: None,
+        }
+    }
+
+    /// Sets the attachments, as a json array.
+    pub fn attachments(mut self, attachments: &str) -> WebhookMessage {
+        self.attachments = Some(attachments.to_string());
+        self
+    }
+
+    /// Sets the blocks, as a json array.
+    pub fn blocks(mut self, blocks: &str) -> WebhookMessage {
+        self.blocks = Some(blocks.to_string());
+        self
+    }
+
+    /// Posts the message as a reply in the thread of thread_ts.
+    pub fn thread_ts(mut self, thread_ts: &str) -> WebhookMessage {
+        self.thread_ts = Some(thread_ts.to_string());
+        self
+    }
+
+    /// Overrides the name shown for legacy webhooks.
+    pub fn username(mut self, username: &str) -> WebhookMessage {
+        self.username = Some(username.to_string());
+        self
+    }
+
+    /// Overrides the icon shown for legacy webhooks with an emoji such as `:ghost:`.
+    pub fn icon_emoji(mut self, icon_emoji: &str) -> WebhookMessage {
+        self.icon_emoji = Some(icon_emoji.to_string());
+        self
+    }
+
+    /// Overrides the icon shown for legacy webhooks with an image.
+    pub fn icon_url(mut self, icon_url: &str) -> WebhookMessage {
+        self.icon_url = Some(icon_url.to_string());
+        self
+    }
+
+    /// Returns the message as json, failing if the attachments or blocks aren't valid json.
+    pub fn to_json(&self) -> Result<Json, Error> {
+        let mut json = BTreeMap::new();
+        json.insert(String::from("text"), Json::String(self.text.clone()));
+        if let Some(ref attachments) = self.attachments {
+            json.insert(String::from("attachments"), try!(Json::from_str(attachments)));
+        }
+        if let Some(ref blocks) = self.blocks {
+            json.insert(String::from("blocks"), try!(Json::from_str(blocks)));
+        }
+        let strings = [("thread_ts", &self.thread_ts),
+                       ("username", &self.username),
+                       ("icon_emoji", &self.icon_emoji),
+                       ("icon_url", &self.icon_url)];
+        for &(name, value) in strings.iter() {
+            if let Some(ref value) = *value {
+                json.insert(name.to_string(), Json::String(value.clone()));
+            }
+        }
+        Ok(Json::Object(json))
+    }
+}
+
+/// An incoming webhook.
+#[derive(Clone, Debug)]
+pub struct Webhook {
+    url: String,
+}
+
+impl Webhook {
+    /// Creates a webhook posting to url, e.g. `https://hooks.slack.com/services/T00/B00/XXX`.
+    pub fn new(url: &str) -> Webhook {
+        Webhook { url: url.to_string() }
+    }
+
+    /// Posts a message. Errors reported by slack, such as `invalid_payload` or
+    /// `channel_not_found`, are returned as `Error::Api` with the matching `ApiErrorCode`.
+    pub fn send(&self, message: &WebhookMessage) -> Result<(), Error> {
+        let body = try!(message.to_json());
+        let client = hyper::Client::new();
+        web::post_json(&client, &self.url, &body)
+    }
+
+    /// Posts a message with only text.
+    pub fn send_text(&self, text: &str) -> Result<(), Error> {
+        self.send(&WebhookMessage::new(text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_json() {
+        let message = WebhookMessage::new("hi")
+                          .blocks(r#"[{"type": "divider"}]"#)
+                          .thread_ts("1503435956.000247")
+                          .icon_emoji(":ghost:");
+        assert_eq!(message.to_json().unwrap().to_string(),
+                   r#"{"blocks":[{"type":"divider"}],"icon_emoji":":ghost:","text":"hi","thread_ts":"1503435956.000247"}"#);
+    }
+
+    #[test]
+    fn invalid_attachments() {
+        assert!(WebhookMessage::new("hi").attachments("{").to_json().is_err());
+    }
+}